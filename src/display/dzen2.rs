@@ -0,0 +1,321 @@
+use std::{borrow::Cow, fmt};
+
+use crate::model::{Alignment, Color};
+
+use super::{
+    CmdlineArgBuilder, DisplayBlock,
+    implementations::{DisplayColor, configured_font},
+    lemonbar::{Geometry, resolve_output_to_geometry},
+    plain::strip_markup,
+};
+
+/// Writes the blocks with dzen2's `^` commands, each alignment starts by moving to its edge or
+/// to the middle of the bar.
+pub struct Dzen2<W> {
+    sink: W,
+    separator: Option<String>,
+    already_wrote_first_block_of_aligment: bool,
+}
+
+pub struct Dzen2Args {
+    height: u32,
+    bottom: bool,
+    output: Option<Geometry>,
+    args: Vec<String>,
+}
+
+impl Default for Dzen2Args {
+    fn default() -> Self {
+        Self {
+            height: 22,
+            bottom: false,
+            output: None,
+            args: vec![
+                "-ta".into(),
+                "l".into(),
+                // dzen2 exits on right clicks by default.
+                "-e".into(),
+                "button3=".into(),
+            ],
+        }
+    }
+}
+
+fn show_c(color: &Color) -> DisplayColor {
+    DisplayColor::new(*color, super::Program::Dzen2)
+}
+
+impl CmdlineArgBuilder for Dzen2Args {
+    fn output(&mut self, name: &str) -> Result<(), String> {
+        self.output = Some(resolve_output_to_geometry(name)?);
+        Ok(())
+    }
+
+    fn height(&mut self, height: u32) {
+        self.height = height;
+    }
+
+    fn bottom(&mut self) {
+        self.bottom = true;
+    }
+
+    fn fonts<'s>(&mut self, mut fonts: impl Iterator<Item = &'s str>) {
+        // the other fonts are picked by name in the blocks.
+        if let Some(font) = fonts.next() {
+            self.args.extend(["-fn".into(), font.into()]);
+        }
+    }
+
+    fn name(&mut self, name: &str) {
+        self.args.extend(["-title-name".into(), name.into()])
+    }
+
+    fn underline_width(&mut self, _width: u32) {}
+
+    fn underline_color(&mut self, _color: &Color) {}
+
+    fn background(&mut self, color: &Color) {
+        self.args.extend(["-bg".into(), show_c(color).to_string()])
+    }
+
+    fn foreground(&mut self, color: &Color) {
+        self.args.extend(["-fg".into(), show_c(color).to_string()])
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.args.extend(["-h".into(), self.height.to_string()]);
+        let height = self.height as i32;
+        match self.output {
+            Some(o) => {
+                let y = if self.bottom {
+                    o.y_offset + o.height - height
+                } else {
+                    o.y_offset
+                };
+                self.args.extend([
+                    "-x".into(),
+                    o.x_offset.to_string(),
+                    "-y".into(),
+                    y.to_string(),
+                    "-w".into(),
+                    o.width.to_string(),
+                ]);
+            }
+            // a negative y is from the bottom of the screen.
+            None if self.bottom => self.args.extend(["-y".into(), (-height).to_string()]),
+            None => {}
+        }
+        self.args
+    }
+}
+
+impl<W: fmt::Write> super::Bar<W> for Dzen2<W> {
+    type BarBlockBuilder<'bar>
+        = Dzen2DisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = Dzen2Args;
+
+    const PROGRAM: &'static str = "dzen2";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        Self {
+            sink,
+            separator,
+            already_wrote_first_block_of_aligment: false,
+        }
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        Dzen2Args::default()
+    }
+
+    fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.already_wrote_first_block_of_aligment = false;
+        match alignment {
+            Alignment::Left => Ok(()),
+            Alignment::Middle => self.sink.write_str("^p(_CENTER)"),
+            Alignment::Right => self.sink.write_str("^p(_RIGHT)"),
+        }
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = self.separator.as_deref().filter(|_| delimit) {
+                self.sink.write_str(&escape(sep))?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        Ok(Dzen2DisplayBlock::new(&mut self.sink))
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = &self.separator {
+                self.sink.write_str(&escape(sep))?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        self.sink.write_str(fragment)
+    }
+
+    fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+pub struct Dzen2DisplayBlock<'bar, W> {
+    sink: &'bar mut W,
+    bg: bool,
+    fg: bool,
+    font: bool,
+    actions: u8,
+}
+
+impl<'bar, W> Dzen2DisplayBlock<'bar, W> {
+    fn new(sink: &'bar mut W) -> Self {
+        Self {
+            sink,
+            bg: false,
+            fg: false,
+            font: false,
+            actions: 0,
+        }
+    }
+}
+
+impl<W> DisplayBlock for Dzen2DisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        write!(self.sink, "^p({})", offset.0)
+    }
+
+    fn bg(&mut self, color: &Color) -> fmt::Result {
+        self.bg = true;
+        write!(self.sink, "^bg({})", show_c(color))
+    }
+
+    fn fg(&mut self, color: &Color) -> fmt::Result {
+        self.fg = true;
+        write!(self.sink, "^fg({})", show_c(color))
+    }
+
+    fn underline(&mut self, _color: &Color) -> fmt::Result {
+        // dzen2 can't underline text.
+        Ok(())
+    }
+
+    fn font(&mut self, font: &crate::model::block::Font<'_>) -> fmt::Result {
+        match configured_font(font) {
+            Some(font) => {
+                self.font = true;
+                write!(self.sink, "^fn({font})")
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn add_action(&mut self, action: crate::event_loop::action_task::Action) -> fmt::Result {
+        // dzen2 runs the command, whose output is read like the clicks of lemonbar.
+        self.actions += 1;
+        write!(
+            self.sink,
+            "^ca({button}, echo {action})",
+            button = action.button,
+        )
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        let body = if raw {
+            strip_markup(body)
+        } else {
+            Cow::Borrowed(body)
+        };
+        self.sink.write_str(&escape(&body))
+    }
+
+    fn finish(self) -> fmt::Result {
+        for _ in 0..self.actions {
+            self.sink.write_str("^ca()")?;
+        }
+        if self.font {
+            self.sink.write_str("^fn()")?;
+        }
+        if self.fg {
+            self.sink.write_str("^fg()")?;
+        }
+        if self.bg {
+            self.sink.write_str("^bg()")?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes `text` show up as is, `^` starts a command in dzen2.
+fn escape(text: &str) -> String {
+    text.replace('^', "^^")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Bar;
+
+    fn screen() -> Geometry {
+        Geometry {
+            width: 1920,
+            height: 1080,
+            x_offset: 1280,
+            y_offset: 100,
+        }
+    }
+
+    #[test]
+    fn escape_doubles_carets() {
+        assert_eq!(escape("^fg(red)^^"), "^^fg(red)^^^^");
+    }
+
+    #[test]
+    fn alignments_move_to_their_edge() {
+        let mut bar = Dzen2::new(String::new(), Some("^".into()));
+        bar.set_alignment(Alignment::Left).unwrap();
+        bar.push_fragment("a").unwrap();
+        bar.push_fragment("b").unwrap();
+        bar.set_alignment(Alignment::Middle).unwrap();
+        bar.push_fragment("c").unwrap();
+        bar.set_alignment(Alignment::Right).unwrap();
+        bar.push_fragment("d").unwrap();
+        assert_eq!(bar.into_inner(), "a^^b^p(_CENTER)c^p(_RIGHT)d");
+    }
+
+    #[test]
+    fn finish_places_the_bar_on_the_output() {
+        let mut args = Dzen2Args {
+            output: Some(screen()),
+            ..Default::default()
+        };
+        args.height(30);
+        args.bottom();
+        assert_eq!(
+            args.finish()[4..],
+            ["-h", "30", "-x", "1280", "-y", "1150", "-w", "1920"]
+        );
+    }
+
+    #[test]
+    fn finish_without_an_output_is_placed_from_the_bottom() {
+        let mut args = Dzen2Args::default();
+        args.bottom();
+        assert_eq!(args.finish()[4..], ["-h", "22", "-y", "-22"]);
+        assert_eq!(Dzen2Args::default().finish()[4..], ["-h", "22"]);
+    }
+}
@@ -0,0 +1,273 @@
+use std::{borrow::Cow, fmt};
+
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    event_loop::{MouseButton, action_task::Action},
+    model::{Alignment, Color},
+};
+
+use super::{DisplayBlock, NoArgs, implementations::DisplayColor, plain::strip_markup};
+
+/// Writes the blocks as the json arrays of the i3bar protocol, which swaybar speaks too. The bar
+/// has no alignments, blocks are shown in order from left to right.
+pub struct I3bar<W> {
+    sink: W,
+    separator: Option<String>,
+    fragment: bool,
+    already_wrote_first_block: bool,
+}
+
+fn show_c(color: &Color) -> DisplayColor {
+    DisplayColor::i3bar(*color)
+}
+
+impl<W: fmt::Write> I3bar<W> {
+    /// Starts a new block object, preceded by the separator if it has to be delimited from the
+    /// previous one.
+    fn delimit(&mut self, delimit: bool) -> fmt::Result {
+        if !self.already_wrote_first_block {
+            self.already_wrote_first_block = true;
+            return Ok(());
+        }
+        self.sink.write_char(',')?;
+        if let Some(sep) = self.separator.as_deref().filter(|_| delimit) {
+            write!(self.sink, "{},", separator_block(sep))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: fmt::Write> super::Bar<W> for I3bar<W> {
+    type BarBlockBuilder<'bar>
+        = I3barDisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = NoArgs;
+
+    const PROGRAM: &'static str = "i3bar";
+
+    const HEADER: &'static str = "{\"version\":1,\"click_events\":true}\n[\n";
+
+    fn new(mut sink: W, separator: Option<String>) -> Self {
+        let _ = sink.write_char('[');
+        Self {
+            sink,
+            separator,
+            fragment: false,
+            already_wrote_first_block: false,
+        }
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self {
+            sink,
+            separator: None,
+            fragment: true,
+            already_wrote_first_block: false,
+        }
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        NoArgs
+    }
+
+    fn set_alignment(&mut self, _: Alignment) -> fmt::Result {
+        Ok(())
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        self.delimit(delimit)?;
+        Ok(I3barDisplayBlock {
+            sink: &mut self.sink,
+            block: Map::new(),
+        })
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        self.delimit(true)?;
+        self.sink.write_str(fragment)
+    }
+
+    fn into_inner(mut self) -> W {
+        if !self.fragment {
+            // the lines are the elements of an endless array.
+            let _ = self.sink.write_str("],");
+        }
+        self.sink
+    }
+
+    fn read_click(line: String) -> Option<String> {
+        #[derive(Deserialize)]
+        struct ClickEvent {
+            instance: Option<String>,
+            button: u8,
+        }
+        let line = line.trim().trim_start_matches(',');
+        // the click events are elements of an endless array too.
+        if line.is_empty() || line == "[" {
+            return None;
+        }
+        let click = match serde_json::from_str::<ClickEvent>(line) {
+            Ok(click) => click,
+            Err(e) => {
+                log::error!("invalid click event '{line}': {e}");
+                return None;
+            }
+        };
+        let mut action = click.instance?.parse::<Action>().ok()?;
+        action.button = MouseButton::try_from(click.button).ok()?;
+        Some(action.to_string())
+    }
+}
+
+fn separator_block(sep: &str) -> Value {
+    json!({
+        "full_text": sep,
+        "separator": false,
+        "separator_block_width": 0,
+    })
+}
+
+pub struct I3barDisplayBlock<'sink, W> {
+    sink: &'sink mut W,
+    block: Map<String, Value>,
+}
+
+impl<W> I3barDisplayBlock<'_, W> {
+    fn set(&mut self, key: &str, value: impl Into<Value>) {
+        self.block.insert(key.into(), value.into());
+    }
+}
+
+impl<W> DisplayBlock for I3barDisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, _offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn bg(&mut self, color: &Color) -> fmt::Result {
+        self.set("background", show_c(color).to_string());
+        Ok(())
+    }
+
+    fn fg(&mut self, color: &Color) -> fmt::Result {
+        self.set("color", show_c(color).to_string());
+        Ok(())
+    }
+
+    fn underline(&mut self, color: &Color) -> fmt::Result {
+        // only the bottom border is drawn, like an underline.
+        self.set("border", show_c(color).to_string());
+        self.set("border_top", 0);
+        self.set("border_left", 0);
+        self.set("border_right", 0);
+        Ok(())
+    }
+
+    fn font(&mut self, _font: &crate::model::block::Font<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn add_action(&mut self, _action: Action) -> fmt::Result {
+        // the clicked button is taken from the click event, the block from its id.
+        Ok(())
+    }
+
+    fn id(&mut self, id: Action) -> fmt::Result {
+        self.set("name", format!("{}-{}", id.id.0 as u8, id.id.1));
+        self.set("instance", id.to_string());
+        Ok(())
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        let body = if raw {
+            strip_markup(body)
+        } else {
+            Cow::Borrowed(body)
+        };
+        self.set("full_text", body.into_owned());
+        Ok(())
+    }
+
+    fn finish(mut self) -> fmt::Result {
+        self.set("markup", "none");
+        self.set("separator", false);
+        self.set("separator_block_width", 0);
+        write!(self.sink, "{}", Value::Object(self.block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Bar;
+
+    fn click(line: &str) -> Option<String> {
+        I3bar::<String>::read_click(line.to_owned())
+    }
+
+    #[test]
+    fn clicks_are_read_from_the_endless_array() {
+        assert_eq!(click("["), None);
+        assert_eq!(click(""), None);
+        assert_eq!(
+            click(r#"{"name":"0-1","instance":"0-1-0-1-0","button":3}"#).as_deref(),
+            Some("0-1-0-3-0")
+        );
+        assert_eq!(
+            click(r#",{"name":"2-0","instance":"2-0-1-1-2-6869","button":1}"#).as_deref(),
+            Some("2-0-1-1-2-6869")
+        );
+    }
+
+    #[test]
+    fn scrolls_are_clicks_of_their_button() {
+        assert_eq!(
+            click(r#",{"instance":"0-0-0-1-0","button":4}"#).as_deref(),
+            Some("0-0-0-4-0")
+        );
+        assert_eq!(
+            click(r#",{"instance":"0-0-0-1-0","button":5}"#).as_deref(),
+            Some("0-0-0-5-0")
+        );
+    }
+
+    #[test]
+    fn invalid_clicks_are_skipped() {
+        assert_eq!(click(",{\"instance\":"), None);
+        assert_eq!(click(r#",{"button":1}"#), None);
+        assert_eq!(click(r#",{"instance":"nope","button":1}"#), None);
+        assert_eq!(click(r#",{"instance":"0-0-0-1-0","button":42}"#), None);
+        assert_eq!(click(r#",{"instance":"7-0-0-1-0","button":1}"#), None);
+    }
+
+    #[test]
+    fn lines_are_elements_of_an_array() {
+        let mut bar = I3bar::new(String::new(), None);
+        let mut block = bar.start_block(true).unwrap();
+        block
+            .id(Action::new(
+                Alignment::Right,
+                2,
+                0,
+                MouseButton::Left,
+                Some(0),
+                None,
+            ))
+            .unwrap();
+        block.text("hi", false).unwrap();
+        block.finish().unwrap();
+        let line = bar.into_inner();
+        let array = line.strip_suffix(',').unwrap();
+        let blocks = serde_json::from_str::<Vec<Map<String, Value>>>(array).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0]["full_text"], "hi");
+        assert_eq!(blocks[0]["name"], "2-2");
+        assert_eq!(blocks[0]["instance"], "2-2-0-1-0");
+    }
+}
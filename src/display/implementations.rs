@@ -1,4 +1,7 @@
-use crate::model::{Alignment, Color};
+use crate::{
+    global_config,
+    model::{Alignment, Color, block::Font},
+};
 use std::fmt::{self, Display};
 
 use super::Program;
@@ -17,16 +20,34 @@ impl DisplayColor {
     pub fn zelbar(color: Color) -> Self {
         Self(color, Program::Zelbar)
     }
+
+    pub fn i3bar(color: Color) -> Self {
+        Self(color, Program::I3bar)
+    }
 }
 
 impl Display for DisplayColor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let Color { r, g, b, a } = self.0;
         match (self.1, a) {
-            (Program::Lemonbar, Some(a)) => write!(f, "#{a:02X}{r:02X}{g:02X}{b:02X}"),
-            (Program::Lemonbar, None) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
             (Program::Zelbar, Some(a)) => write!(f, "0x{r:02X}{g:02X}{b:02X}{a:02X}"),
             (Program::Zelbar, None) => write!(f, "0x{r:02X}{g:02X}{b:02X}"),
+            (Program::Lemonbar | Program::Stdout, Some(a)) => {
+                write!(f, "#{a:02X}{r:02X}{g:02X}{b:02X}")
+            }
+            (Program::I3bar, Some(a)) => write!(f, "#{r:02X}{g:02X}{b:02X}{a:02X}"),
+            // these have no transparency, pango takes it as an attribute of its own.
+            (
+                Program::Dzen2
+                | Program::Xmobar
+                | Program::Tmux
+                | Program::Waybar
+                | Program::Somebar,
+                _,
+            ) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
+            (Program::Lemonbar | Program::Stdout | Program::I3bar, None) => {
+                write!(f, "#{r:02X}{g:02X}{b:02X}")
+            }
         }
     }
 }
@@ -42,3 +63,13 @@ impl Display for Alignment {
         f.write_str(s)
     }
 }
+
+/// The configured font a block picked by its index, `None` for `-`, the default one.
+pub fn configured_font(font: &Font<'_>) -> Option<String> {
+    let index = font.0.parse::<usize>().ok()?;
+    global_config::get()
+        .file_config
+        .fonts
+        .get(index.checked_sub(1)?)
+        .cloned()
+}
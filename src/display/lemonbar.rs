@@ -38,9 +38,9 @@ fn show_c(color: &Color) -> DisplayColor {
 }
 
 impl CmdlineArgBuilder for LemonArgs {
-    fn output(&mut self, name: &str) {
-        self.outputs
-            .push(resolve_output_to_geometry(name).expect("failed to get information from xrandr"))
+    fn output(&mut self, name: &str) -> Result<(), String> {
+        self.outputs.push(resolve_output_to_geometry(name)?);
+        Ok(())
     }
 
     fn height(&mut self, height: u32) {
@@ -105,6 +105,10 @@ impl<W: fmt::Write> super::Bar<W> for Lemonbar<W> {
         }
     }
 
+    fn new_fragment(sink: W, _: crate::model::Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
     fn cmdline_builder() -> Self::CmdlineArgBuilder {
         LemonArgs::default()
     }
@@ -125,6 +129,17 @@ impl<W: fmt::Write> super::Bar<W> for Lemonbar<W> {
         Ok(LemonDisplayBlock::new(&mut self.sink))
     }
 
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = &self.separator {
+                self.sink.write_str(sep)?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        self.sink.write_str(fragment)
+    }
+
     fn into_inner(self) -> W {
         self.sink
     }
@@ -245,21 +260,33 @@ where
     }
 }
 
-struct Geometry {
-    width: i32,
-    x_offset: i32,
+/// Where an output is on the screen.
+pub(super) struct Geometry {
+    pub width: i32,
+    pub height: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
 }
 
-fn resolve_output_to_geometry(name: &str) -> Result<Geometry, xrandr::XrandrError> {
-    let mut handle = xrandr::XHandle::open()?;
-    let monitor = handle
-        .monitors()?
-        .into_iter()
-        .find(|m| m.name == name)
-        .unwrap_or_else(|| panic!("there is no monitor with name {name}"));
+pub(super) fn resolve_output_to_geometry(name: &str) -> Result<Geometry, String> {
+    let monitors = xrandr::XHandle::open()
+        .and_then(|mut handle| handle.monitors())
+        .map_err(|e| format!("failed to get information from xrandr: {e}"))?;
+    let monitor = monitors.iter().find(|m| m.name == name).ok_or_else(|| {
+        format!(
+            "there is no monitor named '{name}', the connected monitors are: {}",
+            monitors
+                .iter()
+                .map(|m| m.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })?;
 
     Ok(Geometry {
         width: monitor.width_px,
+        height: monitor.height_px,
         x_offset: monitor.x,
+        y_offset: monitor.y,
     })
 }
@@ -1,24 +1,50 @@
+pub mod dzen2;
+pub mod i3bar;
 pub mod implementations;
 pub mod lemonbar;
+pub mod pango;
+pub mod plain;
+pub mod tmux;
+pub mod waybar;
+pub mod xmobar;
 pub mod zelbar;
 
 use std::{fmt, str::FromStr};
 
 use crate::{
-    event_loop::action_task::Action,
+    event_loop::{MouseButton, action_task::Action},
     model::{
         Alignment, Color,
-        block::{AvailableActions, Block, BlockText, Font, Offset},
+        block::{Block, BlockText, Font, Offset},
     },
 };
+pub use dzen2::Dzen2;
+pub use i3bar::I3bar;
 pub use lemonbar::Lemonbar;
+pub use pango::Pango;
+pub use plain::Plain;
+pub use tmux::Tmux;
+pub use waybar::Waybar;
+pub use xmobar::Xmobar;
 pub use zelbar::Zelbar;
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Program {
     #[default]
     Lemonbar,
     Zelbar,
+    Dzen2,
+    Xmobar,
+    /// No bar at all, the lines are written to stdout and click actions are read from stdin.
+    Stdout,
+    /// The i3bar protocol, lemon is the `status_command` of i3bar or swaybar.
+    I3bar,
+    /// A waybar custom module with `return-type: json`, it shows a single block.
+    Waybar,
+    /// tmux's status line.
+    Tmux,
+    /// somebar's status, written as pango markup with `somebar -c status`.
+    Somebar,
 }
 
 impl FromStr for Program {
@@ -27,6 +53,13 @@ impl FromStr for Program {
         match s {
             "lemonbar" => Ok(Self::Lemonbar),
             "zelbar" => Ok(Self::Zelbar),
+            "dzen2" => Ok(Self::Dzen2),
+            "xmobar" => Ok(Self::Xmobar),
+            "stdout" => Ok(Self::Stdout),
+            "i3bar" | "swaybar" => Ok(Self::I3bar),
+            "waybar" => Ok(Self::Waybar),
+            "tmux" => Ok(Self::Tmux),
+            "somebar" => Ok(Self::Somebar),
             _ => Err(format!("unsuported program '{s}'")),
         }
     }
@@ -37,6 +70,46 @@ impl Program {
         match self {
             Self::Zelbar => "zelbar",
             Self::Lemonbar => "lemonbar",
+            Self::Dzen2 => "dzen2",
+            Self::Xmobar => "xmobar",
+            Self::Stdout => "stdout",
+            Self::I3bar => "i3bar",
+            Self::Waybar => "waybar",
+            Self::Tmux => "tmux",
+            Self::Somebar => "somebar",
+        }
+    }
+
+    /// Whether the bar is drawn on lemon's own stdout instead of being spawned.
+    pub fn is_headless(self) -> bool {
+        matches!(
+            self,
+            Self::Stdout | Self::I3bar | Self::Waybar | Self::Tmux | Self::Somebar
+        )
+    }
+}
+
+/// The format of the lines written by [Program::Stdout].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// Only the text of the blocks, see [Plain].
+    #[default]
+    Plain,
+    Lemonbar,
+    Zelbar,
+    /// Pango markup, see [Pango].
+    Pango,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Self::Plain),
+            "lemonbar" => Ok(Self::Lemonbar),
+            "zelbar" => Ok(Self::Zelbar),
+            "pango" => Ok(Self::Pango),
+            _ => Err(format!("unsuported dialect '{s}'")),
         }
     }
 }
@@ -50,19 +123,35 @@ pub trait Bar<W: fmt::Write> {
 
     const PROGRAM: &'static str;
 
+    /// Written once before the first line.
+    const HEADER: &'static str = "";
+
     fn new(sink: W, separator: Option<String>) -> Self;
 
+    /// Creates a bar that renders the blocks of a single alignment without the alignment marker
+    /// and separators, so the result can later be passed to [Bar::push_fragment].
+    fn new_fragment(sink: W, alignment: Alignment) -> Self;
+
     fn cmdline_builder() -> Self::CmdlineArgBuilder;
 
     fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result;
 
     fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error>;
 
+    /// Appends a block rendered by a bar created with [Bar::new_fragment], delimiting it from
+    /// the previous block of the same alignment.
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result;
+
     fn into_inner(self) -> W;
+
+    /// Turns a line the bar wrote into a click action, `None` if the line isn't a click.
+    fn read_click(line: String) -> Option<String> {
+        Some(line)
+    }
 }
 
 pub trait CmdlineArgBuilder {
-    fn output(&mut self, name: &str);
+    fn output(&mut self, name: &str) -> Result<(), String>;
     fn height(&mut self, height: u32);
     fn bottom(&mut self);
     fn fonts<'s>(&mut self, fonts: impl Iterator<Item = &'s str>);
@@ -74,6 +163,36 @@ pub trait CmdlineArgBuilder {
     fn finish(self) -> Vec<String>;
 }
 
+/// The arguments of the bars lemon doesn't spawn, there are none to build.
+#[derive(Default)]
+pub struct NoArgs;
+
+impl CmdlineArgBuilder for NoArgs {
+    fn output(&mut self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn height(&mut self, _height: u32) {}
+
+    fn bottom(&mut self) {}
+
+    fn fonts<'s>(&mut self, _fonts: impl Iterator<Item = &'s str>) {}
+
+    fn name(&mut self, _name: &str) {}
+
+    fn underline_width(&mut self, _width: u32) {}
+
+    fn underline_color(&mut self, _color: &Color) {}
+
+    fn background(&mut self, _color: &Color) {}
+
+    fn foreground(&mut self, _color: &Color) {}
+
+    fn finish(self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
 pub trait DisplayBlock {
     fn offset(&mut self, offset: &Offset<'_>) -> fmt::Result;
 
@@ -87,6 +206,12 @@ pub trait DisplayBlock {
 
     fn add_action(&mut self, action: Action) -> fmt::Result;
 
+    /// Identifies the block and segment being drawn, as the action of a left click on them. Only
+    /// bars that report which block was clicked instead of running the actions need it.
+    fn id(&mut self, _id: Action) -> fmt::Result {
+        Ok(())
+    }
+
     fn text(&mut self, body: &str, raw: bool) -> fmt::Result;
 
     fn finish(self) -> fmt::Result;
@@ -113,11 +238,26 @@ pub fn display_block<W: fmt::Write, B: Bar<W>>(
         if let Some(x) = text.decorations.underline.or(block.decorations.underline) {
             builder.underline(&x)?;
         }
-        if let Some(x) = &block.font {
-            builder.font(x)?;
+        if let Some(x) = text.font.as_deref().map(Font).or(block.font) {
+            builder.font(&x)?;
         }
-        for button in AvailableActions::from(block.available_actions.map(|o| o.is_some())).iter() {
-            builder.add_action(Action::new(block.alignment, index, monitor, button))?;
+        builder.id(Action::new(
+            block.alignment,
+            index,
+            monitor,
+            MouseButton::Left,
+            Some(i),
+            text.segment.as_deref(),
+        ))?;
+        for button in block.buttons().pressed().iter() {
+            builder.add_action(Action::new(
+                block.alignment,
+                index,
+                monitor,
+                button,
+                Some(i),
+                text.segment.as_deref(),
+            ))?;
         }
         builder.text(&text.text, block.raw)?;
         builder.finish()?;
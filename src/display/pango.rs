@@ -0,0 +1,228 @@
+use std::fmt;
+
+use crate::model::{Alignment, Color};
+
+use super::{
+    CmdlineArgBuilder, DisplayBlock,
+    implementations::{DisplayColor, configured_font},
+    plain::{TabSeparated, strip_markup},
+};
+
+/// Renders the blocks as pango markup, for the Wayland bars that show it, like somebar's status
+/// or yambar's script module. Alignments are separated by tabs, like [super::Plain].
+pub struct Pango<W>(TabSeparated<W>);
+
+fn show_c(color: &Color) -> DisplayColor {
+    DisplayColor::new(*color, super::Program::Somebar)
+}
+
+impl<W: fmt::Write> super::Bar<W> for Pango<W> {
+    type BarBlockBuilder<'bar>
+        = PangoDisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = SomebarArgs;
+
+    const PROGRAM: &'static str = "somebar";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        Self(TabSeparated::new(sink, separator.as_deref().map(escape)))
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        SomebarArgs
+    }
+
+    fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.0.set_alignment(alignment)
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        Ok(PangoDisplayBlock::new(self.0.start_block(delimit)?))
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        self.0.start_block(true)?.write_str(fragment)
+    }
+
+    fn into_inner(self) -> W {
+        self.0.into_inner()
+    }
+}
+
+/// The arguments of `somebar -c`, which sends a command to the running somebar. Everything else
+/// is configured when somebar is built, so only the line is needed.
+pub struct SomebarArgs;
+
+impl CmdlineArgBuilder for SomebarArgs {
+    /// somebar shows the same status on every output.
+    fn output(&mut self, _name: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn height(&mut self, _height: u32) {}
+
+    fn bottom(&mut self) {}
+
+    fn fonts<'s>(&mut self, _fonts: impl Iterator<Item = &'s str>) {}
+
+    fn name(&mut self, _name: &str) {}
+
+    fn underline_width(&mut self, _width: u32) {}
+
+    fn underline_color(&mut self, _color: &Color) {}
+
+    fn background(&mut self, _color: &Color) {}
+
+    fn foreground(&mut self, _color: &Color) {}
+
+    fn finish(self) -> Vec<String> {
+        vec!["-c".into(), "status".into()]
+    }
+}
+
+/// Writes the text of a block in a `<span>` with its decorations as attributes.
+pub struct PangoDisplayBlock<'sink, W> {
+    sink: &'sink mut W,
+    attributes: String,
+}
+
+impl<'sink, W> PangoDisplayBlock<'sink, W> {
+    pub fn new(sink: &'sink mut W) -> Self {
+        Self {
+            sink,
+            attributes: String::new(),
+        }
+    }
+
+    fn attribute(&mut self, name: &str, value: impl fmt::Display) -> fmt::Result {
+        use fmt::Write as _;
+        write!(self.attributes, " {name}=\"{value}\"")
+    }
+
+    /// Pango colors have no alpha, it's an attribute of its own.
+    fn color(&mut self, name: &str, alpha: &str, color: &Color) -> fmt::Result {
+        self.attribute(name, show_c(color))?;
+        match color.a {
+            Some(a) => self.attribute(alpha, pango_alpha(a)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W> DisplayBlock for PangoDisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, _offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn bg(&mut self, color: &Color) -> fmt::Result {
+        self.color("background", "bgalpha", color)
+    }
+
+    fn fg(&mut self, color: &Color) -> fmt::Result {
+        self.color("foreground", "fgalpha", color)
+    }
+
+    fn underline(&mut self, color: &Color) -> fmt::Result {
+        self.attribute("underline", "single")?;
+        self.attribute("underline_color", show_c(color))
+    }
+
+    fn font(&mut self, font: &crate::model::block::Font<'_>) -> fmt::Result {
+        match configured_font(font) {
+            Some(font) => self.attribute("font", escape(&font)),
+            None => Ok(()),
+        }
+    }
+
+    fn add_action(&mut self, _action: crate::event_loop::action_task::Action) -> fmt::Result {
+        Ok(())
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        let body = if raw {
+            escape(&strip_markup(body))
+        } else {
+            escape(body)
+        };
+        if self.attributes.is_empty() {
+            self.sink.write_str(&body)
+        } else {
+            write!(self.sink, "<span{}>{body}</span>", self.attributes)
+        }
+    }
+
+    fn finish(self) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Makes `text` show up as is in markup.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Pango's alpha goes from 1 to 65535, 0 would be the default opacity.
+fn pango_alpha(a: u8) -> u32 {
+    (a as u32 * 257).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Bar;
+
+    #[test]
+    fn escape_makes_text_markup_safe() {
+        assert_eq!(
+            escape(r#"<b>"Tom" & 'Jerry'</b>"#),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &apos;Jerry&apos;&lt;/b&gt;"
+        );
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn alpha_spans_pangos_range_without_zero() {
+        assert_eq!(pango_alpha(0), 1);
+        assert_eq!(pango_alpha(1), 257);
+        assert_eq!(pango_alpha(128), 32896);
+        assert_eq!(pango_alpha(255), 65535);
+    }
+
+    #[test]
+    fn blocks_are_spans_with_an_escaped_separator() {
+        let mut bar = Pango::new(String::new(), Some(" <|> ".into()));
+        bar.set_alignment(Alignment::Left).unwrap();
+        let mut block = bar.start_block(true).unwrap();
+        block
+            .fg(&Color {
+                a: Some(0),
+                ..Color::RED
+            })
+            .unwrap();
+        block.text("a&b", false).unwrap();
+        block.finish().unwrap();
+        bar.push_fragment("c").unwrap();
+        bar.set_alignment(Alignment::Right).unwrap();
+        bar.push_fragment("d").unwrap();
+        assert_eq!(
+            bar.into_inner(),
+            format!(
+                "<span foreground=\"{}\" fgalpha=\"1\">a&amp;b</span> &lt;|&gt; c\t\td",
+                show_c(&Color::RED)
+            )
+        );
+    }
+}
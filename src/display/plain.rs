@@ -0,0 +1,193 @@
+use std::{borrow::Cow, fmt, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::model::{Alignment, Color};
+
+use super::{DisplayBlock, NoArgs};
+
+/// Renders only the text of the blocks, without any markup. Alignments are separated by tabs,
+/// so that the line is `left\tmiddle\tright`.
+pub struct Plain<W>(TabSeparated<W>);
+
+impl<W: fmt::Write> super::Bar<W> for Plain<W> {
+    type BarBlockBuilder<'bar>
+        = PlainDisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = NoArgs;
+
+    const PROGRAM: &'static str = "stdout";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        Self(TabSeparated::new(sink, separator))
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        NoArgs
+    }
+
+    fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.0.set_alignment(alignment)
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        Ok(PlainDisplayBlock {
+            sink: self.0.start_block(delimit)?,
+        })
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        self.0.start_block(true)?.write_str(fragment)
+    }
+
+    fn into_inner(self) -> W {
+        self.0.into_inner()
+    }
+}
+
+/// The line of the bars that separate their alignments with tabs, the separator is written as is.
+pub(super) struct TabSeparated<W> {
+    sink: W,
+    separator: Option<String>,
+    alignment: Option<Alignment>,
+    already_wrote_first_block_of_aligment: bool,
+}
+
+impl<W: fmt::Write> TabSeparated<W> {
+    pub(super) fn new(sink: W, separator: Option<String>) -> Self {
+        Self {
+            sink,
+            separator,
+            alignment: None,
+            already_wrote_first_block_of_aligment: false,
+        }
+    }
+
+    pub(super) fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.already_wrote_first_block_of_aligment = false;
+        let from = self.alignment.map_or(0, |a| a as usize);
+        for _ in from..alignment as usize {
+            self.sink.write_char('\t')?;
+        }
+        self.alignment = Some(alignment);
+        Ok(())
+    }
+
+    /// Writes the separator unless this is the first block of the alignment, the block goes
+    /// in the returned sink.
+    pub(super) fn start_block(&mut self, delimit: bool) -> Result<&mut W, fmt::Error> {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = self.separator.as_deref().filter(|_| delimit) {
+                self.sink.write_str(sep)?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        Ok(&mut self.sink)
+    }
+
+    pub(super) fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+pub struct PlainDisplayBlock<'sink, W> {
+    sink: &'sink mut W,
+}
+
+impl<W> DisplayBlock for PlainDisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, _offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn bg(&mut self, _color: &Color) -> fmt::Result {
+        Ok(())
+    }
+
+    fn fg(&mut self, _color: &Color) -> fmt::Result {
+        Ok(())
+    }
+
+    fn underline(&mut self, _color: &Color) -> fmt::Result {
+        Ok(())
+    }
+
+    fn font(&mut self, _font: &crate::model::block::Font<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn add_action(&mut self, _action: crate::event_loop::action_task::Action) -> fmt::Result {
+        Ok(())
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        let body = if raw {
+            strip_markup(body)
+        } else {
+            Cow::Borrowed(body)
+        };
+        self.sink.write_str(&body)
+    }
+
+    fn finish(self) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Removes the lemonbar formatting tags from raw text.
+pub(super) fn strip_markup(body: &str) -> Cow<'_, str> {
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    let pat = TAGS.get_or_init(|| Regex::new(r"%\{[^}]*\}").unwrap());
+    pat.replace_all(body, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Bar;
+
+    fn block(bar: &mut Plain<String>, text: &str, raw: bool) {
+        let mut block = bar.start_block(true).unwrap();
+        block.fg(&Color::RED).unwrap();
+        block.text(text, raw).unwrap();
+        block.finish().unwrap();
+    }
+
+    #[test]
+    fn alignments_are_separated_by_tabs() {
+        let mut bar = Plain::new(String::new(), Some(" | ".into()));
+        bar.set_alignment(Alignment::Left).unwrap();
+        block(&mut bar, "a", false);
+        block(&mut bar, "b", false);
+        bar.set_alignment(Alignment::Right).unwrap();
+        block(&mut bar, "c", false);
+        assert_eq!(bar.into_inner(), "a | b\t\tc");
+    }
+
+    #[test]
+    fn raw_text_loses_its_markup() {
+        let mut bar = Plain::new(String::new(), None);
+        bar.set_alignment(Alignment::Middle).unwrap();
+        block(&mut bar, "%{F#ff0000}hot%{F-} %{A:x:}cold%{A}", true);
+        block(&mut bar, "%{F-}", false);
+        assert_eq!(bar.into_inner(), "\thot cold%{F-}");
+    }
+
+    #[test]
+    fn fragments_are_delimited() {
+        let mut bar = Plain::new(String::new(), Some(" | ".into()));
+        bar.set_alignment(Alignment::Left).unwrap();
+        bar.push_fragment("a").unwrap();
+        bar.push_fragment("b").unwrap();
+        assert_eq!(bar.into_inner(), "a | b");
+    }
+}
@@ -0,0 +1,151 @@
+use std::{borrow::Cow, fmt};
+
+use crate::model::{Alignment, Color};
+
+use super::{DisplayBlock, NoArgs, implementations::DisplayColor, plain::strip_markup};
+
+/// Writes the blocks in tmux's status line format, each alignment starts with an
+/// `#[align=...]` marker.
+pub struct Tmux<W> {
+    sink: W,
+    separator: Option<String>,
+    already_wrote_first_block_of_aligment: bool,
+}
+
+fn show_c(color: &Color) -> DisplayColor {
+    DisplayColor::new(*color, super::Program::Tmux)
+}
+
+/// The marker that starts the blocks of `alignment`.
+pub fn align(alignment: Alignment) -> &'static str {
+    match alignment {
+        Alignment::Left => "#[align=left]",
+        Alignment::Middle => "#[align=centre]",
+        Alignment::Right => "#[align=right]",
+    }
+}
+
+impl<W: fmt::Write> super::Bar<W> for Tmux<W> {
+    type BarBlockBuilder<'bar>
+        = TmuxDisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = NoArgs;
+
+    const PROGRAM: &'static str = "tmux";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        Self {
+            sink,
+            separator,
+            already_wrote_first_block_of_aligment: false,
+        }
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        NoArgs
+    }
+
+    fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.already_wrote_first_block_of_aligment = false;
+        self.sink.write_str(align(alignment))
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = self.separator.as_deref().filter(|_| delimit) {
+                self.sink.write_str(&escape(sep))?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        Ok(TmuxDisplayBlock {
+            sink: &mut self.sink,
+            styles: Vec::new(),
+        })
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = &self.separator {
+                self.sink.write_str(&escape(sep))?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        self.sink.write_str(fragment)
+    }
+
+    fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+pub struct TmuxDisplayBlock<'sink, W> {
+    sink: &'sink mut W,
+    styles: Vec<String>,
+}
+
+impl<W> DisplayBlock for TmuxDisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, _offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn bg(&mut self, color: &Color) -> fmt::Result {
+        self.styles.push(format!("bg={}", show_c(color)));
+        Ok(())
+    }
+
+    fn fg(&mut self, color: &Color) -> fmt::Result {
+        self.styles.push(format!("fg={}", show_c(color)));
+        Ok(())
+    }
+
+    fn underline(&mut self, color: &Color) -> fmt::Result {
+        self.styles.push("underscore".into());
+        self.styles.push(format!("us={}", show_c(color)));
+        Ok(())
+    }
+
+    fn font(&mut self, _font: &crate::model::block::Font<'_>) -> fmt::Result {
+        Ok(())
+    }
+
+    fn add_action(&mut self, _action: crate::event_loop::action_task::Action) -> fmt::Result {
+        Ok(())
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        let body = if raw {
+            strip_markup(body)
+        } else {
+            Cow::Borrowed(body)
+        };
+        if self.styles.is_empty() {
+            return self.sink.write_str(&escape(&body));
+        }
+        write!(
+            self.sink,
+            "#[{}]{}#[default]",
+            self.styles.join(","),
+            escape(&body)
+        )
+    }
+
+    fn finish(self) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Makes `text` show up as is, `#` starts a format in tmux.
+fn escape(text: &str) -> String {
+    text.replace('#', "##")
+}
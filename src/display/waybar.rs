@@ -0,0 +1,145 @@
+use std::{fmt, sync::OnceLock};
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::{global_config, model::Alignment};
+
+use super::{
+    NoArgs,
+    pango::{PangoDisplayBlock, escape},
+};
+
+/// Writes the block as the json of a waybar custom module with `return-type: json`, its text
+/// is pango markup.
+pub struct Waybar<W> {
+    sink: W,
+    /// The markup of the blocks, the line is only written once all of it is known.
+    markup: String,
+    separator: Option<String>,
+    fragment: bool,
+    already_wrote_first_block: bool,
+}
+
+impl<W: fmt::Write> super::Bar<W> for Waybar<W> {
+    type BarBlockBuilder<'bar>
+        = PangoDisplayBlock<'bar, String>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = NoArgs;
+
+    const PROGRAM: &'static str = "waybar";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        Self {
+            sink,
+            markup: String::new(),
+            separator,
+            fragment: false,
+            already_wrote_first_block: false,
+        }
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self {
+            fragment: true,
+            ..Self::new(sink, None)
+        }
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        NoArgs
+    }
+
+    fn set_alignment(&mut self, _: Alignment) -> fmt::Result {
+        Ok(())
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        if self.already_wrote_first_block {
+            if let Some(sep) = self.separator.as_deref().filter(|_| delimit) {
+                self.markup.push_str(&escape(sep));
+            }
+        } else {
+            self.already_wrote_first_block = true;
+        }
+        Ok(PangoDisplayBlock::new(&mut self.markup))
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        if self.already_wrote_first_block {
+            if let Some(sep) = &self.separator {
+                self.markup.push_str(&escape(sep));
+            }
+        } else {
+            self.already_wrote_first_block = true;
+        }
+        self.markup.push_str(fragment);
+        Ok(())
+    }
+
+    fn into_inner(mut self) -> W {
+        if self.fragment {
+            let _ = self.sink.write_str(&self.markup);
+            return self.sink;
+        }
+        let mut module = Map::new();
+        if let Some(percentage) = percentage(&self.markup) {
+            module.insert("percentage".into(), percentage.into());
+        }
+        if let Some(block) = &global_config::get().cmdline.block {
+            module.insert("class".into(), class(block).into());
+        }
+        module.insert("tooltip".into(), self.markup.clone().into());
+        module.insert("text".into(), self.markup.into());
+        let _ = write!(self.sink, "{}", Value::Object(module));
+        self.sink
+    }
+}
+
+/// The first percentage in the text of the block, like `42%`.
+fn percentage(markup: &str) -> Option<u8> {
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    static PERCENTAGE: OnceLock<Regex> = OnceLock::new();
+    let tags = TAGS.get_or_init(|| Regex::new(r"<[^>]*>").unwrap());
+    let pat = PERCENTAGE.get_or_init(|| Regex::new(r"(\d+(?:\.\d+)?)\s*%").unwrap());
+    let text = tags.replace_all(markup, "");
+    let n = pat.captures(&text)?[1].parse::<f64>().ok()?;
+    Some(n.round().clamp(0.0, 100.0) as u8)
+}
+
+/// The css class of the module, the title of the block in kebab case.
+fn class(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentage_is_the_first_one_in_the_text() {
+        assert_eq!(percentage("vol 42% 13%"), Some(42));
+        assert_eq!(
+            percentage("<span foreground=\"#ff0000\">99.6 %</span>"),
+            Some(100)
+        );
+        assert_eq!(percentage("150%"), Some(100));
+        assert_eq!(percentage("no percentage"), None);
+        // numbers in the markup aren't part of the text.
+        assert_eq!(percentage("<span font=\"50%\">up</span>"), None);
+    }
+
+    #[test]
+    fn class_is_the_title_in_kebab_case() {
+        assert_eq!(class("Volume"), "volume");
+        assert_eq!(class("CPU temp / fan"), "cpu-temp-fan");
+        assert_eq!(class("  Wi-Fi  "), "wi-fi");
+    }
+}
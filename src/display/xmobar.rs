@@ -0,0 +1,317 @@
+use std::{borrow::Cow, fmt};
+
+use crate::{
+    event_loop::xmobar_task,
+    global_config,
+    model::{Alignment, Color},
+};
+
+use super::{
+    CmdlineArgBuilder, DisplayBlock,
+    implementations::DisplayColor,
+    lemonbar::{Geometry, resolve_output_to_geometry},
+    plain::{TabSeparated, strip_markup},
+};
+
+/// Writes the blocks with xmobar's tags. xmobar only aligns its template, so each alignment is
+/// shown by a reader of its own: the line is split at its tabs by [xmobar_task], the left blocks
+/// go to the `UnsafeStdinReader` and the others to a `PipeReader`.
+pub struct Xmobar<W>(TabSeparated<W>);
+
+pub struct XmobarArgs {
+    height: Option<u32>,
+    bottom: bool,
+    output: Option<Geometry>,
+    args: Vec<String>,
+}
+
+impl Default for XmobarArgs {
+    fn default() -> Self {
+        Self {
+            height: None,
+            bottom: false,
+            output: None,
+            args: vec![
+                "-c".into(),
+                format!(
+                    "[Run UnsafeStdinReader, \
+                     Run PipeReader \"/dev/fd/{}\" \"middle\", \
+                     Run PipeReader \"/dev/fd/{}\" \"right\"]",
+                    xmobar_task::FDS[0],
+                    xmobar_task::FDS[1],
+                ),
+                "-t".into(),
+                "%UnsafeStdinReader%}%middle%{%right%".into(),
+            ],
+        }
+    }
+}
+
+fn show_c(color: &Color) -> DisplayColor {
+    DisplayColor::new(*color, super::Program::Xmobar)
+}
+
+impl CmdlineArgBuilder for XmobarArgs {
+    fn output(&mut self, name: &str) -> Result<(), String> {
+        self.output = Some(resolve_output_to_geometry(name)?);
+        Ok(())
+    }
+
+    fn height(&mut self, height: u32) {
+        self.height = Some(height);
+    }
+
+    fn bottom(&mut self) {
+        self.bottom = true;
+    }
+
+    fn fonts<'s>(&mut self, mut fonts: impl Iterator<Item = &'s str>) {
+        if let Some(font) = fonts.next() {
+            self.args.extend(["-f".into(), font.into()]);
+        }
+        self.args
+            .extend(fonts.flat_map(|font| ["-N".into(), font.into()]))
+    }
+
+    fn name(&mut self, name: &str) {
+        self.args.extend(["-n".into(), name.into()])
+    }
+
+    fn underline_width(&mut self, _width: u32) {}
+
+    fn underline_color(&mut self, _color: &Color) {}
+
+    fn background(&mut self, color: &Color) {
+        self.args.extend(["-B".into(), show_c(color).to_string()])
+    }
+
+    fn foreground(&mut self, color: &Color) {
+        self.args.extend(["-F".into(), show_c(color).to_string()])
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        let edge = if self.bottom { "Bottom" } else { "Top" };
+        let position = match (self.output, self.height) {
+            (Some(o), height) => {
+                let height = height.unwrap_or(22) as i32;
+                let y = if self.bottom {
+                    o.y_offset + o.height - height
+                } else {
+                    o.y_offset
+                };
+                format!(
+                    "Static {{ xpos = {}, ypos = {y}, width = {}, height = {height} }}",
+                    o.x_offset, o.width
+                )
+            }
+            (None, Some(height)) => format!("{edge}H {height}"),
+            (None, None) => edge.into(),
+        };
+        self.args.extend(["-p".into(), position]);
+        self.args
+    }
+}
+
+impl<W: fmt::Write> super::Bar<W> for Xmobar<W> {
+    type BarBlockBuilder<'bar>
+        = XmobarDisplayBlock<'bar, W>
+    where
+        Self: 'bar;
+
+    type CmdlineArgBuilder = XmobarArgs;
+
+    const PROGRAM: &'static str = "xmobar";
+
+    fn new(sink: W, separator: Option<String>) -> Self {
+        let separator = separator.map(|sep| escape(&sep).into_owned());
+        Self(TabSeparated::new(sink, separator))
+    }
+
+    fn new_fragment(sink: W, _: Alignment) -> Self {
+        Self::new(sink, None)
+    }
+
+    fn cmdline_builder() -> Self::CmdlineArgBuilder {
+        XmobarArgs::default()
+    }
+
+    fn set_alignment(&mut self, alignment: Alignment) -> fmt::Result {
+        self.0.set_alignment(alignment)
+    }
+
+    fn start_block(&mut self, delimit: bool) -> Result<Self::BarBlockBuilder<'_>, fmt::Error> {
+        Ok(XmobarDisplayBlock::new(self.0.start_block(delimit)?))
+    }
+
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        self.0.start_block(true)?.write_str(fragment)
+    }
+
+    fn into_inner(self) -> W {
+        self.0.into_inner()
+    }
+}
+
+pub struct XmobarDisplayBlock<'bar, W> {
+    sink: &'bar mut W,
+    bg: Option<Color>,
+    fg: Option<Color>,
+    /// The tags to close once the text is written, innermost last.
+    tags: Vec<&'static str>,
+}
+
+impl<'bar, W> XmobarDisplayBlock<'bar, W> {
+    fn new(sink: &'bar mut W) -> Self {
+        Self {
+            sink,
+            bg: None,
+            fg: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl<W> DisplayBlock for XmobarDisplayBlock<'_, W>
+where
+    W: fmt::Write,
+{
+    fn offset(&mut self, offset: &crate::model::block::Offset<'_>) -> fmt::Result {
+        write!(self.sink, "<hspace={}/>", offset.0)
+    }
+
+    fn bg(&mut self, color: &Color) -> fmt::Result {
+        self.bg = Some(*color);
+        Ok(())
+    }
+
+    fn fg(&mut self, color: &Color) -> fmt::Result {
+        self.fg = Some(*color);
+        Ok(())
+    }
+
+    fn underline(&mut self, color: &Color) -> fmt::Result {
+        let width = global_config::get()
+            .file_config
+            .underline_width
+            .unwrap_or(1);
+        self.tags.push("</box>");
+        write!(
+            self.sink,
+            "<box type=Bottom width={width} color={}>",
+            show_c(color)
+        )
+    }
+
+    fn font(&mut self, font: &crate::model::block::Font<'_>) -> fmt::Result {
+        // the first font is xmobar's main one, the others are its additional fonts.
+        match font.0.parse::<usize>() {
+            Ok(i) => {
+                self.tags.push("</fn>");
+                write!(self.sink, "<fn={}>", i - 1)
+            }
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn add_action(&mut self, action: crate::event_loop::action_task::Action) -> fmt::Result {
+        // xmobar runs the command, whose output is read like the clicks of lemonbar.
+        self.tags.push("</action>");
+        write!(
+            self.sink,
+            "<action=`echo {action}` button={button}>",
+            button = action.button,
+        )
+    }
+
+    fn text(&mut self, body: &str, raw: bool) -> fmt::Result {
+        // both colors go in the same tag, the foreground can't be left out.
+        if self.fg.is_some() || self.bg.is_some() {
+            let fg = self
+                .fg
+                .or(global_config::get().file_config.foreground)
+                .unwrap_or(Color::WHITE);
+            self.tags.push("</fc>");
+            write!(self.sink, "<fc={}", show_c(&fg))?;
+            if let Some(bg) = &self.bg {
+                write!(self.sink, ",{}", show_c(bg))?;
+            }
+            self.sink.write_char('>')?;
+        }
+        let body = if raw {
+            strip_markup(body)
+        } else {
+            Cow::Borrowed(body)
+        };
+        self.sink.write_str(&escape(&body))
+    }
+
+    fn finish(self) -> fmt::Result {
+        self.tags
+            .iter()
+            .rev()
+            .try_for_each(|tag| self.sink.write_str(tag))
+    }
+}
+
+/// Makes `text` show up as is, xmobar shows the contents of a `raw` tag without parsing it. Tabs
+/// separate the alignments, so they become spaces.
+fn escape(text: &str) -> Cow<'_, str> {
+    let text = if text.contains('\t') {
+        Cow::Owned(text.replace('\t', " "))
+    } else {
+        Cow::Borrowed(text)
+    };
+    if text.contains('<') {
+        Cow::Owned(format!("<raw={}:{text}/>", text.chars().count()))
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Geometry {
+        Geometry {
+            width: 1920,
+            height: 1080,
+            x_offset: 1280,
+            y_offset: 100,
+        }
+    }
+
+    #[test]
+    fn escape_wraps_tags_in_raw() {
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("<fc=red>é</fc>"), "<raw=14:<fc=red>é</fc>/>");
+        assert_eq!(escape("a\tb"), "a b");
+    }
+
+    #[test]
+    fn finish_places_the_bar_on_the_output() {
+        let mut args = XmobarArgs {
+            output: Some(screen()),
+            ..Default::default()
+        };
+        args.height(30);
+        args.bottom();
+        assert_eq!(
+            args.finish()[4..],
+            [
+                "-p",
+                "Static { xpos = 1280, ypos = 1150, width = 1920, height = 30 }"
+            ]
+        );
+    }
+
+    #[test]
+    fn finish_without_an_output_sticks_to_an_edge() {
+        let mut args = XmobarArgs::default();
+        args.height(30);
+        assert_eq!(args.finish()[4..], ["-p", "TopH 30"]);
+        let mut args = XmobarArgs::default();
+        args.bottom();
+        assert_eq!(args.finish()[4..], ["-p", "Bottom"]);
+    }
+}
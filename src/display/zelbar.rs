@@ -28,8 +28,9 @@ fn show_c(color: &Color) -> DisplayColor {
 }
 
 impl CmdlineArgBuilder for ZelbarArgs {
-    fn output(&mut self, name: &str) {
+    fn output(&mut self, name: &str) -> Result<(), String> {
         self.args.extend(["-o".into(), name.into()]);
+        Ok(())
     }
 
     fn height(&mut self, height: u32) {
@@ -93,6 +94,13 @@ impl<W: fmt::Write> super::Bar<W> for Zelbar<W> {
         }
     }
 
+    fn new_fragment(sink: W, alignment: Alignment) -> Self {
+        Self {
+            alignment,
+            ..Self::new(sink, None)
+        }
+    }
+
     fn cmdline_builder() -> Self::CmdlineArgBuilder {
         ZelbarArgs::default()
     }
@@ -116,6 +124,18 @@ impl<W: fmt::Write> super::Bar<W> for Zelbar<W> {
         Ok(ZelbarDisplayBlock::new(&mut self.sink))
     }
 
+    fn push_fragment(&mut self, fragment: &str) -> fmt::Result {
+        if self.already_wrote_first_block_of_aligment {
+            if let Some(sep) = &self.separator {
+                write!(self.sink, "{}", self.alignment)?;
+                self.sink.write_str(sep)?;
+            }
+        } else {
+            self.already_wrote_first_block_of_aligment = true;
+        }
+        self.sink.write_str(fragment)
+    }
+
     fn into_inner(self) -> W {
         self.sink
     }
@@ -1,111 +1,172 @@
-use super::{Event, MouseButton, current_layer};
+use super::{Event, MouseButton, Segment, current_layer, events::EventBus};
 use crate::{
+    global_config,
     model::{Alignment, block::BlockId},
     util::cmd,
 };
-use futures::{StreamExt, stream};
+use futures::{Stream, StreamExt};
 use std::{
+    borrow::Cow,
     fmt::{self, Display},
     str::FromStr,
 };
 use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    process::ChildStdout,
-    sync::broadcast::Sender,
+    io, select,
+    time::{self, Instant},
 };
 
-pub async fn run(outputs: Vec<ChildStdout>, events: Sender<Event>) {
-    stream::iter(outputs)
-        .for_each_concurrent(None, |out| {
-            let events = events.clone();
-            async move {
-                let mut out = BufReader::new(out);
-                let mut buf = String::new();
-                loop {
-                    buf.clear();
-                    let action = match out.read_line(&mut buf).await {
-                        Ok(0) => break,
-                        // TODO: zelbar currently only suports one action pre block, as such, the
-                        // syntax is actually %{A:command} instead of %{AX:command} but the parser
-                        // doesn't enforce this, it just assumes there is a `:` after the A and
-                        // skips it. As such, if you output an action in the lemonbar format, the
-                        // command will start with `:` and then you'll get a error running the
-                        // command
-                        //
-                        // This method means that for lemobar no command can start with `:` but
-                        // that's okay since I've never seen such a command (besides `true`).
-                        Ok(_) => {
-                            let buf = buf.trim();
-                            if buf == ":" {
-                                let _ = cmd::run_cmd(
-                                    "action-task",
-                                    buf,
-                                    crate::model::AffectedMonitor::All,
-                                    current_layer(),
-                                )
-                                .await;
-                                continue;
-                            } else {
-                                log::trace!("lembar output: '{buf}'");
-                                match buf.trim_start_matches(':').parse::<Action>() {
-                                    Ok(a) => a,
-                                    Err(e) => {
-                                        if cfg!(debug_assertions) {
-                                            log::error!(
-                                                "Failed to parse buf '{}' because: {}",
-                                                buf,
-                                                e
-                                            );
-                                        }
-                                        let _ = cmd::run_cmd(
-                                            "action-task",
-                                            buf,
-                                            crate::model::AffectedMonitor::All,
-                                            current_layer(),
-                                        )
-                                        .await;
-                                        continue;
-                                    }
-                                }
-                            }
+/// Reads the click actions reported by a bar, one per line. Lines that aren't actions are raw
+/// commands the blocks gave the bar, they're only run if `run_commands`, lines that anyone
+/// can write to lemon are dropped instead.
+///
+/// TODO: long presses, which need the bars to report releases as well as presses.
+pub async fn run(
+    mut lines: impl Stream<Item = io::Result<String>> + Unpin,
+    events: &EventBus,
+    run_commands: bool,
+) {
+    // a click that might still turn into a double click, and until when it can.
+    let mut pending: Option<(Action<'static>, Instant)> = None;
+    loop {
+        let line = match pending.as_ref().map(|(_, deadline)| *deadline) {
+            Some(deadline) => select! {
+                line = lines.next() => line.transpose(),
+                _ = time::sleep_until(deadline) => {
+                    if let Some((single, _)) = pending.take() {
+                        events.send(single.into());
+                    }
+                    continue;
+                }
+            },
+            None => lines.next().await.transpose(),
+        };
+        let action = match line {
+            Ok(None) => break,
+            // TODO: zelbar currently only suports one action pre block, as such, the
+            // syntax is actually %{A:command} instead of %{AX:command} but the parser
+            // doesn't enforce this, it just assumes there is a `:` after the A and
+            // skips it. As such, if you output an action in the lemonbar format, the
+            // command will start with `:` and then you'll get a error running the
+            // command
+            //
+            // This method means that for lemobar no command can start with `:` but
+            // that's okay since I've never seen such a command (besides `true`).
+            Ok(Some(buf)) => {
+                let buf = buf.trim();
+                if buf == ":" && run_commands {
+                    let _ = cmd::run_cmd(
+                        "action-task",
+                        buf,
+                        crate::model::AffectedMonitor::All,
+                        current_layer(),
+                        None,
+                        &[],
+                    )
+                    .await;
+                    continue;
+                } else {
+                    log::trace!("lembar output: '{buf}'");
+                    match buf.trim_start_matches(':').parse::<Action>() {
+                        Ok(a) => a,
+                        Err(e) if !run_commands => {
+                            log::warn!("ignoring click '{buf}': {e}");
+                            continue;
                         }
                         Err(e) => {
-                            log::error!("Error reading from lemonbar: {:?}", e);
+                            if cfg!(debug_assertions) {
+                                log::error!("Failed to parse buf '{}' because: {}", buf, e);
+                            }
+                            let _ = cmd::run_cmd(
+                                "action-task",
+                                buf,
+                                crate::model::AffectedMonitor::All,
+                                current_layer(),
+                                None,
+                                &[],
+                            )
+                            .await;
                             continue;
                         }
-                    };
-                    if events.send(action.into()).is_err() {
-                        break;
                     }
                 }
             }
-        })
-        .await;
+            Err(e) => {
+                log::error!("Error reading from lemonbar: {:?}", e);
+                continue;
+            }
+        };
+        match pending.take() {
+            Some((first, _)) if first.same_spot(&action) => {
+                if let Some(button) = action.button.doubled() {
+                    events.send(Action { button, ..action }.into());
+                }
+                continue;
+            }
+            Some((single, _)) => events.send(single.into()),
+            None => {}
+        }
+        if events.has_double_click(action.id, action.button) {
+            let window = global_config::get().file_config.double_click_interval();
+            pending = Some((action, Instant::now() + window));
+        } else {
+            events.send(action.into());
+        }
+    }
+    if let Some((single, _)) = pending {
+        events.send(single.into());
+    }
 }
 
-pub struct Action {
+pub struct Action<'s> {
     pub id: BlockId,
     pub monitor: u8,
     pub button: MouseButton,
+    /// The position of the clicked text among the texts of the block.
+    pub segment_index: Option<usize>,
+    pub segment: Option<Cow<'s, str>>,
 }
 
-impl Action {
-    pub fn new(alignment: Alignment, index: usize, monitor: u8, button: MouseButton) -> Self {
+impl<'s> Action<'s> {
+    pub fn new(
+        alignment: Alignment,
+        index: usize,
+        monitor: u8,
+        button: MouseButton,
+        segment_index: Option<usize>,
+        segment: Option<&'s str>,
+    ) -> Self {
         Self {
             id: (alignment, index),
             monitor,
             button,
+            segment_index,
+            segment: segment.map(Cow::Borrowed),
         }
     }
 }
 
-impl From<Action> for Event {
-    fn from(a: Action) -> Self {
-        Event::MouseClicked(a.id, a.monitor, a.button)
+impl Action<'_> {
+    /// Whether both clicks were made with the same button on the same segment of a block.
+    fn same_spot(&self, other: &Action<'_>) -> bool {
+        self.id == other.id
+            && self.monitor == other.monitor
+            && self.button == other.button
+            && self.segment_index == other.segment_index
+            && self.segment == other.segment
+    }
+}
+
+impl From<Action<'_>> for Event {
+    fn from(a: Action<'_>) -> Self {
+        let segment = a.segment_index.map(|index| Segment {
+            index,
+            id: a.segment.map(Cow::into_owned),
+        });
+        Event::MouseClicked(a.id, a.monitor, a.button, segment, 1)
     }
 }
 
-impl FromStr for Action {
+impl FromStr for Action<'static> {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = &mut s.trim_end_matches('\n').split('-');
@@ -114,7 +175,7 @@ impl FromStr for Action {
                 s.next()
                     .and_then(|s| s.parse::<u8>().ok())
                     .ok_or("Missing al")?
-                    .into(),
+                    .try_into()?,
                 s.next()
                     .and_then(|s| s.parse().ok())
                     .ok_or("Missing index")?,
@@ -127,17 +188,79 @@ impl FromStr for Action {
                 .next()
                 .and_then(|s| s.parse::<u8>().ok())
                 .ok_or("Missing button")?
-                .into(),
+                .try_into()?,
+            segment_index: s
+                .next()
+                .map(|s| s.parse().map_err(|_| "Invalid segment index"))
+                .transpose()?,
+            segment: s.next().map(decode_segment).transpose()?.map(Cow::Owned),
         })
     }
 }
 
-impl Display for Action {
+impl Display for Action<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{}-{}-{}-{}",
             self.id.0 as u8, self.id.1, self.monitor, self.button
-        )
+        )?;
+        if let Some(index) = self.segment_index {
+            write!(f, "-{index}")?;
+            if let Some(segment) = &self.segment {
+                // segments are hex encoded so they can't clash with the syntax of any bar.
+                f.write_str("-")?;
+                segment.bytes().try_for_each(|b| write!(f, "{b:02x}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_segment(hex: &str) -> Result<String, &'static str> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or("Invalid segment")
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    String::from_utf8(bytes).map_err(|_| "Invalid segment")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[test]
+    fn alignments_out_of_range_are_an_error() {
+        assert_eq!("7-0-0-1".parse::<Action>().err(), Some("Invalid alignment"));
+        assert!("2-0-0-1".parse::<Action>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn lines_that_arent_actions_are_dropped_unless_they_are_commands() {
+        let marker = std::env::temp_dir().join(format!("lemon-action-{}", std::process::id()));
+        let mut events = EventBus::default();
+        let mut block = events.register((Alignment::Right, 0));
+        let lines = [
+            "7-0-0-1".to_owned(),
+            format!("touch '{}'", marker.display()),
+            "2-0-0-1".to_owned(),
+        ];
+        run(stream::iter(lines.map(Ok)), &events, false).await;
+        assert!(matches!(
+            block.recv().await,
+            Some(Event::MouseClicked(
+                (Alignment::Right, 0),
+                0,
+                MouseButton::Left,
+                None,
+                1
+            ))
+        ));
+        assert!(!marker.exists());
     }
 }
@@ -0,0 +1,322 @@
+use super::{
+    Event, MONITOR_COUNT, action_task,
+    events::EventBus,
+    outputs::Outputs,
+    replay::{self, Entry},
+    somebar_task, tmux_task, xmobar_task,
+};
+use crate::{
+    display::{Bar, Program},
+    global_config,
+    model::ActivationLayer,
+    util::cmd::{StderrTail, child_debug_loop},
+};
+use futures::{
+    future::{self, Either},
+    stream::{self, FuturesUnordered, StreamExt},
+};
+use std::{
+    process::Stdio,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use tokio::{
+    io::{self, AsyncBufReadExt as _, AsyncWrite, AsyncWriteExt as _, BufReader},
+    process::{Child, ChildStdout, Command},
+    select,
+    sync::{Mutex, mpsc, watch},
+    time::{self, Instant, timeout},
+};
+use tokio_stream::wrappers::LinesStream;
+use tokio_util::sync::CancellationToken;
+
+/// How long to wait before restarting a bar that died, doubled every time it dies in a row.
+const MIN_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// A bar that stays up for this long is considered healthy and the backoff is reset.
+const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+
+/// Where the lines of a bar are written to.
+pub type BarInput = Box<dyn AsyncWrite + Send + Unpin>;
+
+struct SpawnedBar {
+    child: Child,
+    stdin: BarInput,
+    stdout: ChildStdout,
+    stderr: StderrTail,
+}
+
+fn spawn<B>(args: &[String], monitor: u8) -> io::Result<SpawnedBar>
+where
+    B: Bar<String>,
+{
+    let mut lemonbar = Command::new(B::PROGRAM);
+    log::debug!("spawning {} with args {:?}", B::PROGRAM, args);
+    lemonbar.args(args);
+
+    lemonbar
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    // xmobar reads the blocks of each alignment from a reader of its own.
+    let pipes = match global_config::get().cmdline.program {
+        Program::Xmobar => Some(xmobar_task::pipes(&mut lemonbar)?),
+        _ => None,
+    };
+
+    let mut lemonbar = lemonbar.spawn()?;
+    let stdin = lemonbar.stdin.take().expect("Failed to find lemon stdin");
+    let stdin: BarInput = match pipes {
+        Some(pipes) => {
+            let (input, lines) = io::duplex(4096);
+            tokio::spawn(async move {
+                if let Err(e) = xmobar_task::run(lines, stdin, pipes).await {
+                    log::error!("failed to write to xmobar for bar {monitor}: {e}");
+                }
+            });
+            Box::new(input)
+        }
+        None => Box::new(stdin),
+    };
+
+    let stderr = child_debug_loop(
+        lemonbar.stderr.take().unwrap(),
+        B::PROGRAM,
+        monitor.into(),
+        ActivationLayer::All,
+    );
+    Ok(SpawnedBar {
+        stdin,
+        stdout: lemonbar.stdout.take().expect("Failed to find lemon stdout"),
+        stderr,
+        child: lemonbar,
+    })
+}
+
+/// Keeps one bar running per output, starting and stopping them as outputs come and go.
+pub async fn manage<B>(
+    mut outputs: watch::Receiver<Outputs>,
+    inputs: mpsc::Sender<(u8, BarInput)>,
+    events: &EventBus,
+) where
+    B: Bar<String>,
+{
+    let mut running = Vec::<(Option<String>, CancellationToken)>::new();
+    let mut bars = FuturesUnordered::new();
+    // the blocks already know about the monitors the bars start with.
+    let mut first = true;
+    loop {
+        let wanted = outputs.borrow_and_update().clone();
+        for (monitor, output) in wanted.iter().enumerate() {
+            // a bar whose monitor now refers to another output has to be restarted.
+            match running.get(monitor) {
+                Some((o, _)) if o == output => continue,
+                Some((_, stop)) => stop.cancel(),
+                None => {}
+            }
+            let stop = events.child_token();
+            if global_config::get().cmdline.program.is_headless() {
+                bars.push(Either::Left(headless::<B>(
+                    monitor as u8,
+                    inputs.clone(),
+                    events,
+                    stop.clone(),
+                )));
+            } else {
+                match global_config::get().to_arg_list::<_, B>(output.as_deref()) {
+                    Ok(args) => bars.push(Either::Right(supervise::<B>(
+                        monitor as u8,
+                        args,
+                        inputs.clone(),
+                        events,
+                        stop.clone(),
+                    ))),
+                    Err(e) => log::error!("can't start bar {monitor} on {output:?}: {e}"),
+                }
+            }
+            if monitor < running.len() {
+                running[monitor] = (output.clone(), stop);
+            } else {
+                running.push((output.clone(), stop));
+            }
+        }
+        for (_, stop) in running.drain(wanted.len()..) {
+            stop.cancel();
+        }
+        MONITOR_COUNT.store(wanted.len() as u8, Ordering::Release);
+        replay::record(|| Entry::Outputs(wanted.clone()));
+        super::outputs::set_running(wanted);
+        if !first {
+            events.send(Event::NewMonitors);
+        }
+        first = false;
+        loop {
+            select! {
+                r = outputs.changed() => match r {
+                    Ok(()) => break,
+                    Err(_) => return bars.collect().await,
+                },
+                Some(()) = bars.next() => {}
+                _ = events.cancelled() => return bars.collect().await,
+            }
+        }
+    }
+}
+
+/// Keeps the bar for `monitor` running until `stop` is cancelled.
+///
+/// Every time the bar is (re)started its stdin is sent through `inputs` so the update task can
+/// write to it, and its stdout is read for click actions.
+pub async fn supervise<B>(
+    monitor: u8,
+    args: Vec<String>,
+    inputs: mpsc::Sender<(u8, BarInput)>,
+    events: &EventBus,
+    stop: CancellationToken,
+) where
+    B: Bar<String>,
+{
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        match spawn::<B>(&args, monitor) {
+            Ok(SpawnedBar {
+                mut child,
+                stdin,
+                stdout,
+                stderr,
+            }) => {
+                if inputs.send((monitor, stdin)).await.is_err() {
+                    return kill(monitor, child).await;
+                }
+                let status = select! {
+                    status = child.wait() => status,
+                    _ = action_task::run(
+                        LinesStream::new(BufReader::new(stdout).lines()),
+                        events,
+                        true,
+                    ) => child.wait().await,
+                    _ = stop.cancelled() => return kill(monitor, child).await,
+                };
+                match status {
+                    Ok(status) => log::error!("bar {monitor} exited: {status}"),
+                    Err(e) => log::error!("failed to wait for bar {monitor}: {e}"),
+                }
+                for line in stderr.finish().await {
+                    log::error!("[stderr of {} @ mon:{monitor}] {line}", B::PROGRAM);
+                }
+            }
+            Err(e) => log::error!("failed to start {} for bar {monitor}: {e}", B::PROGRAM),
+        }
+        if started.elapsed() >= HEALTHY_AFTER {
+            backoff = MIN_BACKOFF;
+        }
+        log::info!("restarting bar {monitor} in {backoff:?}");
+        select! {
+            _ = time::sleep(backoff) => {}
+            _ = stop.cancelled() => return,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Draws the bar of `monitor` on lemon's own stdout and reads click actions from its stdin until
+/// `stop` is cancelled, tmux and somebar get their lines through [tmux_task] and [somebar_task]
+/// instead. There is only one stdout, so only the first monitor gets a bar.
+async fn headless<B>(
+    monitor: u8,
+    inputs: mpsc::Sender<(u8, BarInput)>,
+    events: &EventBus,
+    stop: CancellationToken,
+) where
+    B: Bar<String>,
+{
+    if monitor != 0 {
+        log::warn!("only one bar can be written to stdout, bar {monitor} won't be shown");
+        return stop.cancelled().await;
+    }
+    let program = global_config::get().cmdline.program;
+    if matches!(program, Program::Tmux | Program::Somebar) {
+        let (input, lines) = io::duplex(4096);
+        if inputs.send((monitor, Box::new(input))).await.is_err() {
+            return;
+        }
+        if program == Program::Somebar {
+            let args = match global_config::get().to_arg_list::<_, B>(None) {
+                Ok(args) => args,
+                Err(e) => {
+                    log::error!("failed to build the arguments of somebar: {e}");
+                    return stop.cancelled().await;
+                }
+            };
+            return select! {
+                _ = somebar_task::run(lines, B::PROGRAM, &args) => {}
+                _ = stop.cancelled() => {}
+            };
+        }
+        let file = global_config::get().cmdline.tmux_file.clone();
+        return select! {
+            _ = tmux_task::run(lines, file.as_deref()) => {}
+            _ = stop.cancelled() => {}
+        };
+    }
+    let mut stdout = io::stdout();
+    // the bar outlives reloads, so it only gets the header once.
+    static WROTE_HEADER: AtomicBool = AtomicBool::new(false);
+    if !WROTE_HEADER.swap(true, Ordering::AcqRel)
+        && let Err(e) = stdout.write_all(B::HEADER.as_bytes()).await
+    {
+        log::error!("failed to write to stdout: {e}");
+    }
+    if inputs.send((monitor, Box::new(stdout))).await.is_err() {
+        return;
+    }
+    let mut lines = stdin_lines().lock().await;
+    let clicks = stream::poll_fn(|cx| lines.poll_recv(cx))
+        .filter_map(|line| future::ready(line.map(B::read_click).transpose()));
+    select! {
+        // stdin was closed, the bar keeps being drawn.
+        _ = action_task::run(clicks, events, false) => stop.cancelled().await,
+        _ = stop.cancelled() => {}
+    }
+}
+
+/// The lines read from lemon's stdin. They are read by a thread of their own, tokio's stdin
+/// would keep lemon from exiting until it read another line.
+fn stdin_lines() -> &'static Mutex<mpsc::Receiver<io::Result<String>>> {
+    static LINES: OnceLock<Mutex<mpsc::Receiver<io::Result<String>>>> = OnceLock::new();
+    LINES.get_or_init(|| {
+        let (tx, rx) = mpsc::channel(16);
+        thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                if tx.blocking_send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(rx)
+    })
+}
+
+async fn kill(monitor: u8, mut child: Child) {
+    let r = timeout(Duration::from_secs(5), async {
+        child.kill().await?;
+        child.wait().await
+    })
+    .await;
+    match r {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            log::error!("failed to stop bar {monitor}: {e}");
+        }
+        Err(_elapsed) => {
+            log::error!("timedout while stopping bar {monitor}");
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use super::{
+    Event, MouseButton, Segment,
+    internal_task::Internal,
+    replay::{self, Entry},
+};
+use crate::model::block::{BlockId, ClickLimit};
+use std::collections::HashSet;
+use tokio::{
+    select,
+    sync::{broadcast, mpsc, watch},
+    time::{self, Instant},
+};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+/// How many clicks a block can have pending before new ones are dropped.
+const CLICK_BACKLOG: usize = 16;
+
+/// How many refresh requests can be pending before the oldest are dropped.
+const REFRESH_BACKLOG: usize = 16;
+
+/// How many internal actions can be pending before new ones are dropped.
+pub const INTERNAL_BACKLOG: usize = 16;
+
+type Click = (u8, MouseButton, Option<Segment>);
+
+/// Routes events to the blocks that care about them.
+///
+/// Clicks go straight to the channel of the block that was clicked, signals, layer changes and
+/// monitor changes are published as the latest value of a watch channel, so a slow block can only ever miss
+/// intermediate values, never be disconnected. Refresh requests are broadcast, since blocks
+/// make them too.
+pub struct EventBus {
+    clicks: [Vec<mpsc::Sender<Click>>; 3],
+    refresh: broadcast::Sender<BlockId>,
+    signal: watch::Sender<()>,
+    layer: watch::Sender<u16>,
+    monitors: watch::Sender<u8>,
+    internal: mpsc::Sender<Internal>,
+    /// The buttons that have a double click bound, per block.
+    double_clicks: HashSet<(BlockId, MouseButton)>,
+    shutdown: CancellationToken,
+}
+
+impl Default for EventBus {
+    /// A bus whose internal actions go nowhere.
+    fn default() -> Self {
+        Self::new(mpsc::channel(1).0)
+    }
+}
+
+impl EventBus {
+    /// Creates a bus whose blocks request internal actions through `internal`.
+    pub fn new(internal: mpsc::Sender<Internal>) -> Self {
+        Self {
+            clicks: Default::default(),
+            refresh: broadcast::channel(REFRESH_BACKLOG).0,
+            signal: watch::channel(()).0,
+            layer: watch::channel(super::current_layer()).0,
+            monitors: watch::channel(super::monitor_count()).0,
+            internal,
+            double_clicks: HashSet::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Creates the receiving end of the bus for a block. Blocks must be registered in the order
+    /// of their index within their alignment.
+    pub fn register(&mut self, bid: BlockId) -> BlockEvents {
+        let (alignment, index) = bid;
+        let clicks = &mut self.clicks[alignment as usize];
+        debug_assert_eq!(clicks.len(), index, "blocks must be registered in order");
+        let (tx, rx) = mpsc::channel(CLICK_BACKLOG);
+        clicks.push(tx);
+        BlockEvents {
+            bid,
+            clicks: ClickQueue {
+                rx,
+                limits: Default::default(),
+                throttled_until: Default::default(),
+                held: None,
+                deferred: None,
+            },
+            refresh: self.refresh.subscribe(),
+            requests: Requests {
+                refresh: self.refresh.clone(),
+                internal: self.internal.clone(),
+            },
+            signal: self.signal.subscribe(),
+            layer: self.layer.subscribe(),
+            monitors: self.monitors.subscribe(),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+
+    /// Tells the bus that `button` has a double click bound in the block `bid`, so its clicks
+    /// have to wait to find out if they are double clicks.
+    pub fn bind_double_click(&mut self, bid: BlockId, button: MouseButton) {
+        self.double_clicks.insert((bid, button.pressed()));
+    }
+
+    pub fn has_double_click(&self, bid: BlockId, button: MouseButton) -> bool {
+        self.double_clicks.contains(&(bid, button))
+    }
+
+    pub fn send(&self, event: Event) {
+        log::debug!("[{:?}] event {:?} dispatched", chrono::Utc::now(), event);
+        replay::record(|| Entry::Event(event.clone()));
+        match event {
+            Event::Signal => {
+                self.signal.send_replace(());
+            }
+            Event::NewLayer => {
+                self.layer.send_replace(super::current_layer());
+            }
+            Event::NewMonitors => {
+                self.monitors.send_replace(super::monitor_count());
+            }
+            Event::MouseClicked(id, monitor, button, segment, _) => {
+                self.click(id, (monitor, button, segment))
+            }
+            Event::Refresh(id) => {
+                let _ = self.refresh.send(id);
+            }
+        }
+    }
+
+    fn click(&self, (alignment, index): BlockId, click: Click) {
+        let Some(block) = self.clicks[alignment as usize].get(index) else {
+            return log::warn!("click on unknown block {:?}", (alignment, index));
+        };
+        match block.try_send(click) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full((_, button, _))) => {
+                log::warn!("block {alignment:?}:{index} is busy, dropping {button:?} click");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                log::debug!("block {alignment:?}:{index} is no longer running");
+            }
+        }
+    }
+
+    /// Tells every block and bar to stop.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Completes once [EventBus::shutdown] is called.
+    pub fn cancelled(&self) -> WaitForCancellationFuture<'_> {
+        self.shutdown.cancelled()
+    }
+
+    /// A token that is cancelled when lemon is shutting down or when the token is itself
+    /// cancelled.
+    pub fn child_token(&self) -> CancellationToken {
+        self.shutdown.child_token()
+    }
+}
+
+/// The events a single block receives.
+pub struct BlockEvents {
+    bid: BlockId,
+    clicks: ClickQueue,
+    refresh: broadcast::Receiver<BlockId>,
+    requests: Requests,
+    signal: watch::Receiver<()>,
+    layer: watch::Receiver<u16>,
+    monitors: watch::Receiver<u8>,
+    shutdown: CancellationToken,
+}
+
+/// What a block can ask of the rest of lemon, it can be moved to the tasks the block spawns.
+#[derive(Clone)]
+pub struct Requests {
+    refresh: broadcast::Sender<BlockId>,
+    internal: mpsc::Sender<Internal>,
+}
+
+impl Requests {
+    /// Asks the block `bid` to run its command again.
+    pub fn refresh(&self, bid: BlockId) {
+        let _ = self.refresh.send(bid);
+    }
+
+    /// Asks the event loop to do something lemon handles itself.
+    pub fn internal(&self, request: Internal) {
+        if let Err(e) = self.internal.try_send(request) {
+            log::warn!("dropping internal action: {e}");
+        }
+    }
+}
+
+impl BlockEvents {
+    /// Waits for the next event, returns `None` once lemon is shutting down.
+    ///
+    /// This method is cancel safe.
+    pub async fn recv(&mut self) -> Option<Event> {
+        select! {
+            biased;
+            _ = self.shutdown.cancelled() => None,
+            Some(((monitor, button, segment), count)) = self.clicks.recv() => {
+                Some(Event::MouseClicked(self.bid, monitor, button, segment, count))
+            }
+            () = refresh_requested(&mut self.refresh, self.bid) => Some(Event::Refresh(self.bid)),
+            Ok(()) = self.layer.changed() => Some(Event::NewLayer),
+            Ok(()) = self.monitors.changed() => Some(Event::NewMonitors),
+            Ok(()) = self.signal.changed() => Some(Event::Signal),
+        }
+    }
+
+    /// Holds clicks back according to the limits of each button.
+    pub fn limit_clicks(&mut self, limits: [ClickLimit; MouseButton::COUNT]) {
+        self.clicks.limits = limits;
+    }
+
+    pub fn requests(&self) -> Requests {
+        self.requests.clone()
+    }
+
+    /// A token that is cancelled when lemon is shutting down.
+    pub fn shutdown(&self) -> CancellationToken {
+        self.shutdown.clone()
+    }
+
+    /// Changes whenever a monitor is plugged in or out, independently of [BlockEvents::recv].
+    pub fn monitors(&self) -> watch::Receiver<u8> {
+        self.monitors.clone()
+    }
+}
+
+/// The clicks of a block, throttled and debounced. All the state is kept here so that
+/// [ClickQueue::recv] is cancel safe.
+struct ClickQueue {
+    rx: mpsc::Receiver<Click>,
+    limits: [ClickLimit; MouseButton::COUNT],
+    /// Until when clicks of each button are throttled.
+    throttled_until: [Option<Instant>; MouseButton::COUNT],
+    /// A click that is held back, how many clicks it stands for and when it is let through.
+    held: Option<(Click, u32, Instant)>,
+    /// A click that arrived while another one was held back.
+    deferred: Option<Click>,
+}
+
+impl ClickQueue {
+    /// Waits for the next click that is let through, along with how many clicks it stands for.
+    async fn recv(&mut self) -> Option<(Click, u32)> {
+        loop {
+            if let Some((_, _, ready_at)) = self.held {
+                select! {
+                    _ = time::sleep_until(ready_at) => return self.release(),
+                    click = self.rx.recv() => match click {
+                        Some(click) => {
+                            if let Some(released) = self.hold_again(click) {
+                                return Some(released);
+                            }
+                        }
+                        None => return self.release(),
+                    },
+                }
+            } else {
+                let click = match self.deferred.take() {
+                    Some(click) => click,
+                    None => self.rx.recv().await?,
+                };
+                if let Some(click) = self.hold(click) {
+                    return Some((click, 1));
+                }
+            }
+        }
+    }
+
+    fn limit(&self, button: MouseButton) -> ClickLimit {
+        self.limits[button as usize - 1]
+    }
+
+    /// Holds back a click if its button is limited, returns it if it can go through right away.
+    fn hold(&mut self, click: Click) -> Option<Click> {
+        let button = click.1;
+        let limit = self.limit(button);
+        let now = Instant::now();
+        match self.throttled_until[button as usize - 1] {
+            Some(until) if now < until && limit.accumulate => {
+                self.held = Some((click, 1, until));
+                None
+            }
+            Some(until) if now < until => None,
+            _ => match limit.debounce {
+                Some(debounce) => {
+                    self.held = Some((click, 1, now + debounce));
+                    None
+                }
+                None => {
+                    self.throttle(button);
+                    Some(click)
+                }
+            },
+        }
+    }
+
+    /// Merges a click into the one being held back if they are on the same spot, otherwise the
+    /// held click is released and the new one waits for its turn.
+    fn hold_again(&mut self, click: Click) -> Option<(Click, u32)> {
+        let (held, count, ready_at) = self.held.as_mut()?;
+        if *held != click {
+            self.deferred = Some(click);
+            return self.release();
+        }
+        *count += 1;
+        if let Some(debounce) = self.limits[held.1 as usize - 1].debounce {
+            *ready_at = (*ready_at).max(Instant::now() + debounce);
+        }
+        None
+    }
+
+    fn release(&mut self) -> Option<(Click, u32)> {
+        let (click, count, _) = self.held.take()?;
+        self.throttle(click.1);
+        let count = if self.limit(click.1).accumulate {
+            count
+        } else {
+            1
+        };
+        Some((click, count))
+    }
+
+    fn throttle(&mut self, button: MouseButton) {
+        if let Some(throttle) = self.limit(button).throttle {
+            self.throttled_until[button as usize - 1] = Some(Instant::now() + throttle);
+        }
+    }
+}
+
+/// Completes once `bid` is asked to refresh, refreshes of other blocks are skipped.
+async fn refresh_requested(refresh: &mut broadcast::Receiver<BlockId>, bid: BlockId) {
+    loop {
+        match refresh.recv().await {
+            Ok(id) if id == bid => return,
+            Ok(_) => {}
+            // the request might have been one of the dropped ones.
+            Err(broadcast::error::RecvError::Lagged(_)) => return,
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Alignment;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn clicks_only_reach_the_clicked_block() {
+        let mut bus = EventBus::default();
+        let mut first = bus.register((Alignment::Left, 0));
+        let mut second = bus.register((Alignment::Left, 1));
+        bus.send(Event::MouseClicked(
+            (Alignment::Left, 1),
+            0,
+            MouseButton::Left,
+            None,
+            1,
+        ));
+        assert!(matches!(
+            second.recv().await,
+            Some(Event::MouseClicked(
+                (Alignment::Left, 1),
+                0,
+                MouseButton::Left,
+                None,
+                1
+            ))
+        ));
+        assert!(first.clicks.rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn busy_block_drops_clicks_but_keeps_running() {
+        let mut bus = EventBus::default();
+        let mut block = bus.register((Alignment::Right, 0));
+        for _ in 0..CLICK_BACKLOG * 2 {
+            bus.send(Event::MouseClicked(
+                (Alignment::Right, 0),
+                0,
+                MouseButton::ScrollUp,
+                None,
+                1,
+            ));
+        }
+        bus.send(Event::Signal);
+        bus.send(Event::Signal);
+        for _ in 0..CLICK_BACKLOG {
+            assert!(matches!(block.recv().await, Some(Event::MouseClicked(..))));
+        }
+        assert!(matches!(block.recv().await, Some(Event::Signal)));
+        bus.shutdown();
+        assert!(block.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn refreshes_only_reach_the_requested_block() {
+        let mut bus = EventBus::default();
+        let mut first = bus.register((Alignment::Left, 0));
+        let mut second = bus.register((Alignment::Left, 1));
+        first.requests().refresh((Alignment::Left, 1));
+        assert!(matches!(
+            second.recv().await,
+            Some(Event::Refresh((Alignment::Left, 1)))
+        ));
+        let pending = tokio::time::timeout(Duration::from_millis(10), first.recv()).await;
+        assert!(pending.is_err());
+    }
+
+    #[tokio::test]
+    async fn debounced_clicks_accumulate() {
+        let mut bus = EventBus::default();
+        let mut block = bus.register((Alignment::Left, 0));
+        let mut limits = [ClickLimit::default(); MouseButton::COUNT];
+        limits[MouseButton::ScrollUp as usize - 1] = ClickLimit {
+            debounce: Some(Duration::from_millis(20)),
+            accumulate: true,
+            ..Default::default()
+        };
+        block.limit_clicks(limits);
+        let click = |button| Event::MouseClicked((Alignment::Left, 0), 0, button, None, 1);
+        for _ in 0..3 {
+            bus.send(click(MouseButton::ScrollUp));
+        }
+        bus.send(click(MouseButton::Left));
+        assert!(matches!(
+            block.recv().await,
+            Some(Event::MouseClicked(_, 0, MouseButton::ScrollUp, None, 3))
+        ));
+        assert!(matches!(
+            block.recv().await,
+            Some(Event::MouseClicked(_, 0, MouseButton::Left, None, 1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn throttled_clicks_are_dropped() {
+        let mut bus = EventBus::default();
+        let mut block = bus.register((Alignment::Left, 0));
+        let mut limits = [ClickLimit::default(); MouseButton::COUNT];
+        limits[MouseButton::ScrollDown as usize - 1].throttle = Some(Duration::from_secs(60));
+        block.limit_clicks(limits);
+        for _ in 0..3 {
+            bus.send(Event::MouseClicked(
+                (Alignment::Left, 0),
+                0,
+                MouseButton::ScrollDown,
+                None,
+                1,
+            ));
+        }
+        assert!(matches!(
+            block.recv().await,
+            Some(Event::MouseClicked(_, 0, MouseButton::ScrollDown, None, 1))
+        ));
+        let pending = tokio::time::timeout(Duration::from_millis(10), block.recv()).await;
+        assert!(pending.is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use super::{
+    CURRENT_LAYER, Event, Exit, current_layer,
+    events::EventBus,
+    replay::{self, Entry},
+};
+use crate::{global_config, model::block::BlockId};
+use std::{collections::HashMap, sync::atomic::Ordering};
+use tokio::sync::mpsc;
+
+/// Click actions that start with this are handled by lemon instead of being run with bash.
+pub const PREFIX: &str = "lemon:";
+
+/// Something lemon can do without spawning a shell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Internal {
+    Layer(LayerChange),
+    /// Runs the command of the block with this title again.
+    Refresh(String),
+    /// Hides the block with this title, or shows it again.
+    Toggle(String),
+    /// Reads the config file again and restarts every block and bar.
+    Reload,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerChange {
+    Next,
+    Prev,
+    Set(u16),
+}
+
+/// Handles the internal actions requested by the blocks until one of them stops the event loop.
+pub async fn run(
+    mut requests: mpsc::Receiver<Internal>,
+    events: &EventBus,
+    ids: HashMap<&'static str, BlockId>,
+    toggles: mpsc::Sender<BlockId>,
+) -> Exit {
+    let find = |title: &str| {
+        let id = ids.get(title).copied();
+        if id.is_none() {
+            log::warn!("there is no block named '{title}'");
+        }
+        id
+    };
+    while let Some(request) = requests.recv().await {
+        log::debug!("internal action {request:?}");
+        match request {
+            Internal::Layer(change) => change_layer(events, change),
+            Internal::Refresh(title) => {
+                if let Some(id) = find(&title) {
+                    events.send(Event::Refresh(id));
+                }
+            }
+            Internal::Toggle(title) => {
+                if let Some(id) = find(&title) {
+                    let _ = toggles.send(id).await;
+                }
+            }
+            Internal::Reload => return Exit::Reload,
+            Internal::Quit => return Exit::Quit,
+        }
+    }
+    std::future::pending().await
+}
+
+pub fn change_layer(events: &EventBus, change: LayerChange) {
+    let n_layers = global_config::get().n_layers;
+    if let LayerChange::Set(l) = change
+        && l >= n_layers
+    {
+        return log::warn!("there is no layer {l}, the last one is {}", n_layers - 1);
+    }
+    CURRENT_LAYER
+        .fetch_update(Ordering::Release, Ordering::Relaxed, move |c| {
+            Some(match change {
+                LayerChange::Next => (c + 1) % n_layers,
+                LayerChange::Prev => (c + n_layers - 1) % n_layers,
+                LayerChange::Set(l) => l,
+            })
+        })
+        .unwrap();
+    replay::record(|| Entry::Layer(current_layer()));
+    events.send(Event::NewLayer);
+}
@@ -1,51 +1,104 @@
 pub mod action_task;
+pub mod bar_task;
+pub mod events;
+pub mod internal_task;
+pub mod outputs;
+pub mod replay;
 pub mod signal_task;
+pub mod somebar_task;
+pub mod tmux_task;
 pub mod update_task;
+pub mod waybar_task;
+pub mod xmobar_task;
 
+use self::events::EventBus;
 use crate::{
-    display::Bar,
+    display::{Bar, Program, display_block},
+    global_config::GlobalConfig,
     model::{
-        ActivationLayer, AffectedMonitor, Alignment, Config,
+        AffectedMonitor, Alignment, Config,
         block::{self, Block, BlockId, BlockText},
     },
-    util::{cmd::child_debug_loop, one_or_more::OneOrMore},
+    util::one_or_more::OneOrMore,
 };
-use futures::{StreamExt as _, stream};
+use serde::{Deserialize, Serialize};
 use std::{
-    ffi::OsStr,
     ops::{Index, IndexMut},
-    process::Stdio,
-    sync::atomic::{AtomicU16, Ordering},
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, AtomicU16, Ordering},
+    },
     time::Duration,
 };
 use tokio::{
-    process::{Child, ChildStdin, ChildStdout, Command},
     select,
-    sync::{broadcast, mpsc},
+    sync::{mpsc, watch},
     task::JoinHandle,
     time::timeout,
 };
 
-#[derive(Debug, Clone, Copy)]
+/// The buttons the bars report, plus double clicks, which lemon detects itself. Bars only
+/// report presses, so there is no way to tell a long press apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MouseButton {
     Left = 1,
     Middle = 2,
     Right = 3,
     ScrollUp = 4,
     ScrollDown = 5,
+    ScrollLeft = 6,
+    ScrollRight = 7,
+    Back = 8,
+    Forward = 9,
+    DoubleLeft = 10,
+    DoubleMiddle = 11,
+    DoubleRight = 12,
+}
+
+impl MouseButton {
+    pub const COUNT: usize = 12;
+
+    /// The button that is pressed, twice for double clicks.
+    pub fn pressed(self) -> Self {
+        match self {
+            Self::DoubleLeft => Self::Left,
+            Self::DoubleMiddle => Self::Middle,
+            Self::DoubleRight => Self::Right,
+            b => b,
+        }
+    }
+
+    /// The double click of this button, if it has one.
+    pub fn doubled(self) -> Option<Self> {
+        match self {
+            Self::Left => Some(Self::DoubleLeft),
+            Self::Middle => Some(Self::DoubleMiddle),
+            Self::Right => Some(Self::DoubleRight),
+            _ => None,
+        }
+    }
 }
 
-impl From<u8> for MouseButton {
-    fn from(x: u8) -> Self {
+impl TryFrom<u8> for MouseButton {
+    type Error = &'static str;
+    fn try_from(x: u8) -> Result<Self, Self::Error> {
         use MouseButton::*;
-        match x {
+        Ok(match x {
             1 => Left,
             2 => Middle,
             3 => Right,
             4 => ScrollUp,
             5 => ScrollDown,
-            _ => unreachable!("Invalid mouse button number (must be inside 1..=5) {}", x),
-        }
+            6 => ScrollLeft,
+            7 => ScrollRight,
+            8 => Back,
+            9 => Forward,
+            10 => DoubleLeft,
+            11 => DoubleMiddle,
+            12 => DoubleRight,
+            _ => return Err("Invalid mouse button (must be inside 1..=12)"),
+        })
     }
 }
 
@@ -55,61 +108,42 @@ impl std::fmt::Display for MouseButton {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
     /// The update signal was received
     Signal,
     /// The current layer changed
     NewLayer,
+    /// A monitor was plugged in or out, so the number of bars changed
+    NewMonitors,
     /// Mouse button clicked
     /// .0: the id of the clicked block
     /// .1: the monitor where the block was clicked
     /// .2: the mouse button used
-    MouseClicked(BlockId, u8, MouseButton),
+    /// .3: the segment of the block that was clicked, if the bar could tell
+    /// .4: how many clicks were held back and merged into this one, see [ClickLimit]
+    ///
+    /// [ClickLimit]: crate::model::block::ClickLimit
+    MouseClicked(BlockId, u8, MouseButton, Option<Segment>, u32),
+    /// A click action asked the block to run its command again
+    Refresh(BlockId),
 }
 
-fn spawn_bar<A, S, W, B>(args: A, monitor: u8) -> (Child, ChildStdin, ChildStdout)
-where
-    A: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-    B: Bar<W>,
-    W: std::fmt::Write,
-{
-    let mut lemonbar = if std::env::var("USE_CAT").is_ok() {
-        let mut cat = Command::new("bash");
-        cat.args(["-c", "cat >&2"]);
-        cat
-    } else {
-        let mut lemonbar = Command::new(B::PROGRAM);
-        if log::log_enabled!(log::Level::Debug) {
-            let args = args.into_iter().collect::<Vec<_>>();
-            let args = args.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
-            log::debug!("spawning {} with args {:?}", B::PROGRAM, args);
-            lemonbar.args(args);
-        } else {
-            lemonbar.args(args);
-        }
-        lemonbar
-    };
-
-    lemonbar
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-    let mut lemonbar = lemonbar.spawn().expect("Couldn't start lemonbar");
+/// One of the texts a block showed, see [BlockText](crate::model::block::BlockText).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    /// Its position among the texts of the block.
+    pub index: usize,
+    /// The segment the block gave it, if any.
+    pub id: Option<String>,
+}
 
-    child_debug_loop(
-        lemonbar.stderr.take().unwrap(),
-        B::PROGRAM,
-        monitor.into(),
-        ActivationLayer::All,
-    );
-    let (le_in, le_out) = (
-        lemonbar.stdin.take().expect("Failed to find lemon stdin"),
-        lemonbar.stdout.take().expect("Failed to find lemon stdout"),
-    );
-    (lemonbar, le_in, le_out)
+/// Why the event loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Quit,
+    /// The config should be read again and the event loop restarted.
+    Reload,
 }
 
 static CURRENT_LAYER: AtomicU16 = AtomicU16::new(0);
@@ -118,25 +152,87 @@ pub fn current_layer() -> u16 {
     CURRENT_LAYER.load(Ordering::Acquire)
 }
 
+static MONITOR_COUNT: AtomicU8 = AtomicU8::new(1);
+
+/// How many bars are running, one per monitor.
+pub fn monitor_count() -> u8 {
+    MONITOR_COUNT.load(Ordering::Acquire)
+}
+
 struct RunningBlock {
     block: Block<'static>,
     last_run: OneOrMore<Vec<BlockText>>,
+    /// The last run rendered for each bar, `None` if it has to be rendered again.
+    fragments: Vec<Option<String>>,
+    /// Whether the block was hidden with an internal action.
+    hidden: bool,
+}
+
+impl RunningBlock {
+    fn fragment<B>(&mut self, index: usize, monitor: u8) -> &str
+    where
+        B: Bar<String>,
+    {
+        let m = usize::from(monitor);
+        if self.fragments.len() <= m {
+            self.fragments.resize(m + 1, None);
+        }
+        self.fragments[m].get_or_insert_with(|| {
+            let mut bar = B::new_fragment(String::new(), self.block.alignment);
+            let text = self.last_run.get(m).map(Vec::as_slice).unwrap_or_default();
+            display_block(&mut bar, &self.block, text, index, monitor).unwrap();
+            bar.into_inner()
+        })
+    }
+
+    fn invalidate(&mut self, monitor: AffectedMonitor) {
+        match monitor {
+            AffectedMonitor::Single(m) => {
+                if let Some(f) = self.fragments.get_mut(usize::from(m)) {
+                    *f = None;
+                }
+            }
+            AffectedMonitor::All => self.fragments.clear(),
+        }
+    }
 }
 
-struct RunningConfig([Vec<RunningBlock>; 3]);
+struct RunningConfig {
+    blocks: [Vec<RunningBlock>; 3],
+    /// The layer and global config the cached fragments were rendered with.
+    rendered_with: Option<(u16, Arc<GlobalConfig>)>,
+}
 
 impl From<Config<'static>> for RunningConfig {
     fn from(value: Config<'static>) -> Self {
-        Self(value.0.map(|monitor_blocks| {
-            monitor_blocks
-                .into_iter()
-                .map(|block| {
-                    let mut last_run = OneOrMore::default();
-                    block.active_in.resize_one_or_more(&mut last_run);
-                    RunningBlock { block, last_run }
-                })
-                .collect()
-        }))
+        Self {
+            blocks: value.0.map(|monitor_blocks| {
+                monitor_blocks
+                    .into_iter()
+                    .map(|block| {
+                        let mut last_run = OneOrMore::default();
+                        block.active_in.resize_one_or_more(&mut last_run);
+                        RunningBlock {
+                            block,
+                            last_run,
+                            fragments: Vec::new(),
+                            hidden: false,
+                        }
+                    })
+                    .collect()
+            }),
+            rendered_with: None,
+        }
+    }
+}
+
+impl From<RunningConfig> for Config<'static> {
+    fn from(value: RunningConfig) -> Self {
+        Self(
+            value
+                .blocks
+                .map(|blocks| blocks.into_iter().map(|b| b.block).collect()),
+        )
     }
 }
 
@@ -144,93 +240,133 @@ impl Index<Alignment> for RunningConfig {
     type Output = Vec<RunningBlock>;
 
     fn index(&self, a: Alignment) -> &Self::Output {
-        &self.0[a as usize]
+        &self.blocks[a as usize]
     }
 }
 
 impl IndexMut<Alignment> for RunningConfig {
     fn index_mut(&mut self, a: Alignment) -> &mut Self::Output {
-        &mut self.0[a as usize]
+        &mut self.blocks[a as usize]
     }
 }
 
 impl RunningConfig {
     pub fn update(&mut self, update: block::BlockUpdate) -> bool {
         let (alignment, index, monitor) = update.id();
+        let running = &mut self[alignment][index];
+        // a monitor might have been plugged in since the state was created.
+        running
+            .block
+            .active_in
+            .resize_one_or_more(&mut running.last_run);
         // if we have to update something that affects all monitors than we assume that `last_run`
         // in the `OneOrMore::One` state.
-        let block = &mut self[alignment][index].last_run[match monitor {
-            AffectedMonitor::Single(n) => n,
-            AffectedMonitor::All => u8::MAX,
-        }];
+        let Some(block) = running.last_run.get_mut(match monitor {
+            AffectedMonitor::Single(n) => n.into(),
+            AffectedMonitor::All => usize::MAX,
+        }) else {
+            log::debug!("dropping update for unplugged monitor {monitor}");
+            return false;
+        };
         let new_block = update.into_inner_text();
         if *block != new_block {
             log::debug!("bar update '{new_block:?}' from {:?}", (alignment, index));
             block.clone_from(&new_block);
+            running.invalidate(monitor);
             true
         } else {
             false
         }
     }
+
+    /// Hides the block if it's shown and shows it if it's hidden.
+    fn toggle(&mut self, (alignment, index): BlockId) {
+        let running = &mut self[alignment][index];
+        running.hidden = !running.hidden;
+    }
+
+    /// Makes room for the state of monitors that were plugged in.
+    fn resize_monitors(&mut self) {
+        for b in self.blocks.iter_mut().flatten() {
+            b.block.active_in.resize_one_or_more(&mut b.last_run);
+        }
+    }
+
+    /// Drops every cached fragment if they were rendered for another layer or global config.
+    fn invalidate_stale_fragments(&mut self, layer: u16, global_config: &Arc<GlobalConfig>) {
+        let fresh = self
+            .rendered_with
+            .as_ref()
+            .is_some_and(|(l, g)| *l == layer && Arc::ptr_eq(g, global_config));
+        if !fresh {
+            self.blocks
+                .iter_mut()
+                .flatten()
+                .for_each(|b| b.invalidate(AffectedMonitor::All));
+            self.rendered_with = Some((layer, global_config.clone()));
+        }
+    }
 }
 
-pub async fn start_event_loop<B>(config: Config<'static>, events: broadcast::Sender<Event>)
+/// Runs the blocks of `config` until lemon quits or reloads, then gives the blocks back so they
+/// can be started again if the reloaded config can't be parsed.
+pub async fn start_event_loop<B>(config: Config<'static>) -> (Exit, Config<'static>)
 where
     B: Bar<String>,
 {
-    let global_config = crate::global_config::get();
-    let (mut bars, mut lemon_inputs, lemon_outputs) = if global_config.cmdline.outputs.is_empty() {
-        let (bar, lemon_inputs, lemon_outputs) =
-            spawn_bar::<_, _, _, B>(&global_config.to_arg_list::<_, B>(None), 0);
-        (vec![bar], vec![lemon_inputs], vec![lemon_outputs])
-    } else {
-        unzip_n::unzip_n!(3);
-        global_config
-            .cmdline
-            .outputs
-            .iter()
-            .map(|g| global_config.to_arg_list::<_, B>(Some(g)))
-            .enumerate()
-            .map(|(i, args)| spawn_bar::<_, _, _, B>(args, i as u8))
-            .unzip_n()
-    };
+    // a reloaded config might have less layers.
+    let n_layers = crate::global_config::get().n_layers;
+    let _ = CURRENT_LAYER.fetch_update(Ordering::Release, Ordering::Relaxed, |l| {
+        (l >= n_layers).then_some(0)
+    });
+    replay::record(|| replay::Entry::Layer(current_layer()));
+    let configured = crate::global_config::get().cmdline.outputs.clone();
+    outputs::warn_missing(&configured).await;
+    let active = outputs::active(&configured).await;
+    // blocks need to know how many monitors there are before they start.
+    MONITOR_COUNT.store(active.len() as u8, Ordering::Release);
     let (updates_tx, updates_rx) = mpsc::channel(100);
-    let blocks_task = tokio::spawn(config.start_blocks(&events, updates_tx));
-    {
+    let (inputs_tx, inputs_rx) = mpsc::channel(active.len().max(1));
+    let (outputs_tx, outputs_rx) = watch::channel(active);
+    let (internal_tx, internal_rx) = mpsc::channel(events::INTERNAL_BACKLOG);
+    let (toggles_tx, toggles_rx) = mpsc::channel(events::INTERNAL_BACKLOG);
+    let mut events = EventBus::new(internal_tx);
+    let blocks_task = tokio::spawn(config.start_blocks(&mut events, updates_tx));
+    let ids = config.ids();
+    let cmdline = crate::global_config::get().cmdline.clone();
+    // a waybar module only runs the block it shows.
+    let waybar_block = cmdline
+        .block
+        .as_deref()
+        .filter(|_| cmdline.program == Program::Waybar)
+        // parsing already failed if there is no such block.
+        .map(|title| (title, ids[title]));
+    let bars = bar_task::manage::<B>(outputs_rx, inputs_tx, &events);
+    let mut bars = pin!(bars);
+    let mut config = RunningConfig::from(config);
+    let exit = {
         select! {
-            _ = update_task::update::<B>(config, updates_rx, &mut lemon_inputs) => {}
-            _ = action_task::run(lemon_outputs, events.clone()) => {}
-            _ = signal_task::refresh(events.clone()) => {}
-            _ = signal_task::layer(events.clone()) => {}
-            _ = stream::iter(&mut bars).for_each(|b| async { let _ = b.wait().await; }) => {}
-            _ = signal_task::graceful_shutdown() => {}
+            _ = update_task::update::<B>(&mut config, updates_rx, inputs_rx, toggles_rx, false) => Exit::Quit,
+            _ = &mut bars => Exit::Quit,
+            _ = outputs::watch(configured, outputs_tx) => Exit::Quit,
+            _ = signal_task::refresh(&events) => Exit::Quit,
+            _ = signal_task::layer(&events) => Exit::Quit,
+            exit = internal_task::run(internal_rx, &events, ids, toggles_tx) => exit,
+            _ = waybar_task::run(&events, waybar_block) => Exit::Quit,
+            _ = signal_task::graceful_shutdown() => Exit::Quit,
         }
-    }
-    cleanup(events, bars, blocks_task).await;
+    };
+    cleanup(&events, bars, blocks_task).await;
+    (exit, config.into())
 }
 
 pub async fn cleanup(
-    events: broadcast::Sender<Event>,
-    bars: Vec<Child>,
+    events: &EventBus,
+    bars: impl Future<Output = ()>,
     blocks_task: JoinHandle<()>,
 ) {
-    drop(events); // signal to all blocks that they should shutdown.
-    for (i, mut c) in bars.into_iter().enumerate() {
-        let r = timeout(Duration::from_secs(5), async {
-            c.kill().await?;
-            c.wait().await
-        })
-        .await;
-        match r {
-            Ok(Ok(_)) => {}
-            Ok(Err(e)) => {
-                log::error!("failed to stop bar {i}: {e}");
-            }
-            Err(_elapsed) => {
-                log::error!("timedout while stopping bar {i}");
-            }
-        }
-    }
+    events.shutdown(); // signal to all blocks and bars that they should shutdown.
+    bars.await;
     if let Err(e) = timeout(Duration::from_secs(6), blocks_task).await {
         log::error!("blocks task panicked: {e}");
     }
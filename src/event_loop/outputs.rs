@@ -0,0 +1,335 @@
+use crate::model::AffectedMonitor;
+use std::{sync::RwLock, thread, time::Duration};
+use tokio::{
+    sync::{mpsc, watch},
+    time,
+};
+
+/// How often the connected monitors are checked when neither hyprland nor RandR can report
+/// their changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The outputs that bars are drawn on, indexed by monitor. `None` means the bar was not given
+/// an output.
+pub type Outputs = Vec<Option<String>>;
+
+/// The outputs of the bars that are currently running.
+static RUNNING: RwLock<Outputs> = RwLock::new(Vec::new());
+
+/// The name of the output the bar of `monitor` is drawn on, empty if it wasn't given one.
+pub fn name_of(monitor: AffectedMonitor) -> String {
+    let AffectedMonitor::Single(m) = monitor else {
+        return String::new();
+    };
+    RUNNING
+        .read()
+        .unwrap()
+        .get(usize::from(m))
+        .cloned()
+        .flatten()
+        .unwrap_or_default()
+}
+
+pub(super) fn set_running(outputs: Outputs) {
+    *RUNNING.write().unwrap() = outputs;
+}
+
+/// The output to configure to get one bar per connected monitor.
+pub const AUTO: &str = "auto";
+
+#[derive(Debug)]
+pub struct Monitor {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The monitors that are currently connected, ordered by their position from left to right.
+pub async fn connected() -> Result<Vec<Monitor>, String> {
+    let mut monitors = query().await?;
+    monitors.sort_by_key(|m| (m.x, m.y));
+    Ok(monitors)
+}
+
+async fn query() -> Result<Vec<Monitor>, String> {
+    #[cfg(feature = "hyprland")]
+    if hypr::is_running() {
+        return hypr::connected().await.map_err(|e| e.to_string());
+    }
+    tokio::task::spawn_blocking(|| {
+        let mut handle = xrandr::XHandle::open()?;
+        Ok::<_, xrandr::XrandrError>(
+            handle
+                .monitors()?
+                .into_iter()
+                .map(|m| Monitor {
+                    name: m.name,
+                    x: m.x,
+                    y: m.y,
+                })
+                .collect(),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("failed to get information from xrandr: {e}"))
+}
+
+/// The configured outputs that are currently connected, or a single bar without an output if
+/// none were configured.
+pub async fn active(configured: &[String]) -> Outputs {
+    if configured.is_empty() {
+        return vec![None];
+    }
+    match connected().await {
+        Ok(connected) if is_auto(configured) => {
+            connected.into_iter().map(|m| Some(m.name)).collect()
+        }
+        Ok(connected) => configured
+            .iter()
+            .filter(|o| connected.iter().any(|m| m.name == **o))
+            .cloned()
+            .map(Some)
+            .collect(),
+        Err(e) if is_auto(configured) => {
+            log::error!("failed to get the connected monitors: {e}");
+            vec![None]
+        }
+        Err(e) => {
+            log::error!("failed to get the connected monitors: {e}");
+            configured.iter().cloned().map(Some).collect()
+        }
+    }
+}
+
+/// Warns about configured outputs that don't exist, their bars will only start once they are
+/// connected.
+pub async fn warn_missing(configured: &[String]) {
+    if configured.is_empty() || is_auto(configured) {
+        return;
+    }
+    let Ok(connected) = connected().await else {
+        return;
+    };
+    for o in configured {
+        if !connected.iter().any(|m| m.name == *o) {
+            log::warn!(
+                "there is no monitor named '{o}', the connected monitors are: {}",
+                connected
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+}
+
+fn is_auto(configured: &[String]) -> bool {
+    matches!(configured, [o] if o == AUTO)
+}
+
+/// Publishes the active outputs every time a monitor is plugged in or out.
+pub async fn watch(configured: Vec<String>, outputs: watch::Sender<Outputs>) {
+    if configured.is_empty() {
+        return std::future::pending().await;
+    }
+    let update = || async {
+        let active = active(&configured).await;
+        // monitors are briefly all gone while they're being rearranged, the bars are kept until
+        // one of them is back.
+        if active.is_empty() {
+            log::debug!("none of the outputs {configured:?} are connected, keeping the bars");
+            return;
+        }
+        outputs.send_if_modified(|current| {
+            let changed = *current != active;
+            if changed {
+                log::info!("outputs changed: {active:?}");
+                *current = active;
+            }
+            changed
+        });
+    };
+    #[cfg(feature = "hyprland")]
+    if hypr::is_running() {
+        hypr::on_monitor_change(&update).await;
+        log::warn!("polling for monitor changes instead");
+    }
+    #[cfg(not(feature = "hyprland"))]
+    let hyprland = false;
+    #[cfg(feature = "hyprland")]
+    let hyprland = hypr::is_running();
+    if !hyprland {
+        let (changes, mut rx) = mpsc::channel(1);
+        // Xlib blocks while it waits for events, so they're read by a thread of their own.
+        thread::spawn(move || {
+            if let Err(e) = randr::listen(changes) {
+                log::error!("failed to listen for RandR events: {e}");
+            }
+        });
+        while rx.recv().await.is_some() {
+            update().await;
+        }
+        log::warn!("polling for monitor changes instead");
+    }
+    loop {
+        time::sleep(POLL_INTERVAL).await;
+        update().await;
+    }
+}
+
+mod randr {
+    use std::{
+        ffi::{c_char, c_int, c_long, c_ulong, c_void},
+        ptr,
+    };
+    use tokio::sync::mpsc::{self, error::TrySendError};
+
+    type Display = c_void;
+    type Window = c_ulong;
+
+    /// Xlib's `XEvent`, a union whose first field is always the type of the event.
+    #[repr(C)]
+    struct XEvent {
+        kind: c_int,
+        pad: [c_long; 24],
+    }
+
+    #[link(name = "X11")]
+    unsafe extern "C" {
+        fn XOpenDisplay(name: *const c_char) -> *mut Display;
+        fn XCloseDisplay(display: *mut Display) -> c_int;
+        fn XDefaultRootWindow(display: *mut Display) -> Window;
+        fn XNextEvent(display: *mut Display, event: *mut XEvent) -> c_int;
+    }
+
+    #[link(name = "Xrandr")]
+    unsafe extern "C" {
+        fn XRRQueryExtension(
+            display: *mut Display,
+            event_base: *mut c_int,
+            error_base: *mut c_int,
+        ) -> c_int;
+        fn XRRSelectInput(display: *mut Display, window: Window, mask: c_int);
+        fn XRRUpdateConfiguration(event: *mut XEvent) -> c_int;
+    }
+
+    const RR_SCREEN_CHANGE_NOTIFY_MASK: c_int = 1 << 0;
+    const RR_OUTPUT_CHANGE_NOTIFY_MASK: c_int = 1 << 2;
+    /// Offsets from the extension's event base.
+    const RR_SCREEN_CHANGE_NOTIFY: c_int = 0;
+    const RR_NOTIFY: c_int = 1;
+
+    struct Connection(*mut Display);
+
+    impl Drop for Connection {
+        fn drop(&mut self) {
+            // SAFETY: the display was opened by XOpenDisplay and isn't used after this.
+            unsafe { XCloseDisplay(self.0) };
+        }
+    }
+
+    /// Sends to `changes` every time RandR reports that the screen or one of its outputs
+    /// changed, until `changes` is closed. Blocks while it waits for them.
+    pub fn listen(changes: mpsc::Sender<()>) -> Result<(), String> {
+        // SAFETY: the display is only used by this thread, and closed once it returns.
+        unsafe {
+            let display = XOpenDisplay(ptr::null());
+            if display.is_null() {
+                return Err("can't open the X display".into());
+            }
+            let display = Connection(display);
+            let (mut event_base, mut error_base) = (0, 0);
+            if XRRQueryExtension(display.0, &mut event_base, &mut error_base) == 0 {
+                return Err("the X server doesn't support RandR".into());
+            }
+            XRRSelectInput(
+                display.0,
+                XDefaultRootWindow(display.0),
+                RR_SCREEN_CHANGE_NOTIFY_MASK | RR_OUTPUT_CHANGE_NOTIFY_MASK,
+            );
+            let mut event = XEvent {
+                kind: 0,
+                pad: [0; 24],
+            };
+            loop {
+                XNextEvent(display.0, &mut event);
+                let kind = event.kind - event_base;
+                if kind != RR_SCREEN_CHANGE_NOTIFY && kind != RR_NOTIFY {
+                    continue;
+                }
+                XRRUpdateConfiguration(&mut event);
+                // a change that is already pending covers this one too.
+                if let Err(TrySendError::Closed(())) = changes.try_send(()) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "hyprland")]
+mod hypr {
+    use futures::FutureExt;
+    use hyprland::{
+        data::Monitors, error::HyprError, event_listener::AsyncEventListener, shared::HyprData,
+    };
+    use tokio::sync::mpsc;
+
+    use super::Monitor;
+
+    pub fn is_running() -> bool {
+        std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some()
+    }
+
+    pub async fn connected() -> Result<Vec<Monitor>, HyprError> {
+        Ok(Monitors::get_async()
+            .await?
+            .into_iter()
+            .map(|m| Monitor {
+                name: m.name,
+                x: m.x,
+                y: m.y,
+            })
+            .collect())
+    }
+
+    /// Calls `f` every time hyprland reports a monitor being added or removed.
+    pub async fn on_monitor_change<F, Fut>(f: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let (tx, mut rx) = mpsc::channel(1);
+        let mut listener = AsyncEventListener::new();
+        let added = tx.clone();
+        listener.add_monitor_added_handler(move |_| {
+            let added = added.clone();
+            async move {
+                let _ = added.try_send(());
+            }
+            .boxed()
+        });
+        listener.add_monitor_removed_handler(move |_| {
+            let removed = tx.clone();
+            async move {
+                let _ = removed.try_send(());
+            }
+            .boxed()
+        });
+        let changes = async {
+            while rx.recv().await.is_some() {
+                f().await;
+            }
+        };
+        tokio::select! {
+            _ = changes => {}
+            r = listener.start_listener_async() => {
+                if let Err(e) = r {
+                    log::error!("failed to listen for hyprland monitor events: {e}");
+                }
+            }
+        }
+    }
+}
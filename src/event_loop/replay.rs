@@ -0,0 +1,293 @@
+use super::{
+    CURRENT_LAYER, Event, MONITOR_COUNT, RunningConfig, bar_task::BarInput, outputs, update_task,
+};
+use crate::{
+    display::Bar,
+    model::{
+        Alignment, Config,
+        block::{BlockId, BlockUpdate},
+    },
+};
+use enum_iterator::IntoEnumIterator;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    sync::{OnceLock, atomic::Ordering},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{self, File},
+    io::{self, AsyncWriteExt as _, BufWriter},
+    sync::mpsc,
+    time,
+};
+
+/// Something that changed what the bars show, or an event the blocks received.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Entry {
+    Update(BlockUpdate),
+    Event(Event),
+    Layer(u16),
+    Toggle(BlockId),
+    Outputs(outputs::Outputs),
+    /// The bar of a monitor was (re)started.
+    Bar(u8),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Recorded {
+    /// Milliseconds since the recording started.
+    at: u64,
+    entry: Entry,
+}
+
+struct Recording {
+    entries: mpsc::UnboundedSender<Recorded>,
+    started: Instant,
+}
+
+static RECORDING: OnceLock<Recording> = OnceLock::new();
+
+impl Recording {
+    /// Creates `path`, the entries are written to it by the returned future.
+    async fn create(path: &Path) -> io::Result<(Self, impl Future<Output = ()> + use<>)> {
+        let file = File::create(path).await?;
+        let (entries, rx) = mpsc::unbounded_channel();
+        let recording = Self {
+            entries,
+            started: Instant::now(),
+        };
+        Ok((recording, write_entries(BufWriter::new(file), rx)))
+    }
+
+    fn record(&self, entry: Entry) {
+        let recorded = Recorded {
+            at: self.started.elapsed().as_millis() as u64,
+            entry,
+        };
+        // the writer only stops after logging why.
+        let _ = self.entries.send(recorded);
+    }
+}
+
+/// Writes one json object per line, flushing whenever it caught up with the entries.
+async fn write_entries(mut file: BufWriter<File>, mut entries: mpsc::UnboundedReceiver<Recorded>) {
+    while let Some(recorded) = entries.recv().await {
+        let written = async {
+            let mut line = serde_json::to_vec(&recorded)?;
+            line.push(b'\n');
+            file.write_all(&line).await?;
+            if entries.is_empty() {
+                file.flush().await?;
+            }
+            io::Result::Ok(())
+        };
+        if let Err(e) = written.await {
+            log::error!("failed to record, stopping the recording: {e}");
+            return;
+        }
+    }
+    if let Err(e) = file.flush().await {
+        log::error!("failed to record: {e}");
+    }
+}
+
+/// Starts writing every [Entry] to `path`, one json object per line.
+pub async fn start_recording(path: &Path) -> io::Result<()> {
+    let (recording, writer) = Recording::create(path).await?;
+    RECORDING
+        .set(recording)
+        .map_err(|_| io::Error::other("lemon is already recording"))?;
+    tokio::spawn(writer);
+    Ok(())
+}
+
+/// Records the entry made by `entry`, which is only called if lemon is recording.
+pub fn record(entry: impl FnOnce() -> Entry) {
+    if let Some(recording) = RECORDING.get() {
+        recording.record(entry());
+    }
+}
+
+/// Shows a recording made with [start_recording] again, writing the lines of every bar to
+/// stdout. `speed` is how many times faster than it was recorded.
+///
+/// Blocks don't run, `config` is only used to render their updates.
+pub async fn replay<B>(config: Config<'static>, path: &Path, speed: f64) -> io::Result<()>
+where
+    B: Bar<String>,
+{
+    let recording = fs::read_to_string(path).await?;
+    let entries = recording
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(serde_json::from_str::<Recorded>)
+        .collect::<Result<Vec<_>, _>>()?;
+    play::<B>(config, entries, speed, || Box::new(io::stdout())).await;
+    Ok(())
+}
+
+/// Feeds `entries` to the bars, whose lines are written to what `output` makes every time a bar
+/// was started.
+async fn play<B>(
+    config: Config<'static>,
+    entries: Vec<Recorded>,
+    speed: f64,
+    output: impl Fn() -> BarInput,
+) where
+    B: Bar<String>,
+{
+    let block_counts = Alignment::into_enum_iter()
+        .map(|a| config[a].len())
+        .collect::<Vec<_>>();
+    let known = |(alignment, index): BlockId| index < block_counts[alignment as usize];
+
+    let (updates_tx, updates_rx) = mpsc::channel(100);
+    let (inputs_tx, inputs_rx) = mpsc::channel::<(u8, BarInput)>(1);
+    let (toggles_tx, toggles_rx) = mpsc::channel(1);
+    let feed = async move {
+        let started = time::Instant::now();
+        let mut wrote_header = false;
+        for Recorded { at, entry } in entries {
+            time::sleep_until(started + Duration::from_millis(at).div_f64(speed)).await;
+            match entry {
+                Entry::Update(u) => {
+                    let (alignment, index, _) = u.id();
+                    if !known((alignment, index)) {
+                        log::warn!("the config has no block {:?}", (alignment, index));
+                        continue;
+                    }
+                    let _ = updates_tx.send(u).await;
+                }
+                Entry::Toggle(bid) if known(bid) => {
+                    let _ = toggles_tx.send(bid).await;
+                }
+                Entry::Toggle(bid) => log::warn!("the config has no block {bid:?}"),
+                Entry::Layer(l) => CURRENT_LAYER.store(l, Ordering::Release),
+                Entry::Outputs(o) => {
+                    MONITOR_COUNT.store(o.len() as u8, Ordering::Release);
+                    outputs::set_running(o);
+                }
+                Entry::Bar(m) => {
+                    let mut output = output();
+                    // like the bars lemon writes to itself, the header is only written once.
+                    if !wrote_header {
+                        wrote_header = true;
+                        if let Err(e) = output.write_all(B::HEADER.as_bytes()).await {
+                            log::error!("failed to write the header: {e}");
+                        }
+                    }
+                    let _ = inputs_tx.send((m, output)).await;
+                }
+                // the blocks aren't running, so there is nothing to send them to.
+                Entry::Event(_) => {}
+            }
+            // the bars take from each channel in any order, so every entry is taken before the
+            // next one is sent. This also keeps the channels open until everything was drawn.
+            let _ = inputs_tx.reserve().await;
+            let _ = toggles_tx.reserve().await;
+            let _ = updates_tx.reserve_many(updates_tx.max_capacity()).await;
+        }
+    };
+    let mut config = RunningConfig::from(config);
+    tokio::join!(
+        // every recorded update is its own frame, even if the replay is faster.
+        update_task::update::<B>(&mut config, updates_rx, inputs_rx, toggles_rx, true),
+        feed
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        display::{I3bar, Plain},
+        model::AffectedMonitor,
+        parsing::{lock_global_config, parse},
+    };
+    use tokio::io::AsyncReadExt as _;
+
+    const HI_THERE: &[(&str, Alignment)] = &[("hi", Alignment::Left), ("there", Alignment::Right)];
+
+    /// Records the `updates` of a left and a right block, then replays them with `B`.
+    async fn round_trip<B: Bar<String>>(name: &str, updates: &[(&str, Alignment)]) -> String {
+        let config = {
+            let _config = lock_global_config();
+            parse(
+                r#"
+# Bar
+- height: 20
+
+# left
+- cmd: `true`
+- interval: 1
+- align: left
+
+# right
+- cmd: `true`
+- interval: 1
+- align: right
+            "#,
+                Default::default(),
+            )
+            .unwrap()
+        };
+        let path = std::env::temp_dir().join(format!("lemon-{name}-{}.jsonl", std::process::id()));
+        let (recording, writer) = Recording::create(&path).await.unwrap();
+        let writer = tokio::spawn(writer);
+        recording.record(Entry::Outputs(vec![None]));
+        recording.record(Entry::Bar(0));
+        for &(text, alignment) in updates {
+            let update = (text.to_owned(), (alignment, 0), AffectedMonitor::All);
+            recording.record(Entry::Update(update.into()));
+        }
+        drop(recording);
+        writer.await.unwrap();
+        let recorded = fs::read_to_string(&path).await.unwrap();
+        let _ = fs::remove_file(&path).await;
+        let entries = recorded
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries.len(), updates.len() + 2);
+
+        let (tx, mut rx) = io::duplex(4096);
+        let tx = std::sync::Mutex::new(Some(tx));
+        play::<B>(config, entries, 100.0, || {
+            Box::new(tx.lock().unwrap().take().unwrap())
+        })
+        .await;
+        let mut lines = String::new();
+        rx.read_to_string(&mut lines).await.unwrap();
+        lines
+    }
+
+    #[tokio::test]
+    async fn replays_the_recorded_lines() {
+        let lines = round_trip::<Plain<_>>("plain", HI_THERE).await;
+        assert_eq!(lines.lines().last(), Some("hi\t\tthere"));
+    }
+
+    #[tokio::test]
+    async fn replays_every_update_of_a_block() {
+        let updates = [
+            ("one", Alignment::Left),
+            ("two", Alignment::Left),
+            ("two", Alignment::Left),
+        ];
+        let lines = round_trip::<Plain<_>>("every", &updates).await;
+        assert_eq!(lines.lines().collect::<Vec<_>>(), ["", "one", "two", "two"]);
+    }
+
+    #[tokio::test]
+    async fn replays_start_with_the_header() {
+        let lines = round_trip::<I3bar<_>>("i3bar", HI_THERE).await;
+        let body = lines.strip_prefix(I3bar::<String>::HEADER).unwrap();
+        assert!(
+            body.lines()
+                .last()
+                .unwrap()
+                .contains(r#""full_text":"there""#)
+        );
+    }
+}
@@ -1,39 +1,31 @@
-use super::{CURRENT_LAYER, Event};
-use crate::global_config;
-use std::{future::pending, sync::atomic::Ordering};
+use super::{
+    Event,
+    events::EventBus,
+    internal_task::{self, LayerChange},
+};
+use std::future::pending;
 use tokio::{
     select,
     signal::unix::{SignalKind, signal},
-    sync::broadcast::Sender,
 };
 
-pub async fn refresh(events: Sender<Event>) {
+pub async fn refresh(events: &EventBus) {
     let mut signals = match signal(SignalKind::user_defined1()) {
         Ok(s) => s,
         Err(e) => panic!("signal task failed: {:?}", e),
     };
     while signals.recv().await.is_some() {
-        if events.send(Event::Signal).is_err() {
-            break;
-        }
+        events.send(Event::Signal);
     }
 }
 
-pub async fn layer(events: Sender<Event>) {
+pub async fn layer(events: &EventBus) {
     let mut signals = match signal(SignalKind::user_defined2()) {
         Ok(s) => s,
         Err(e) => panic!("layer task failed: {:?}", e),
     };
     while signals.recv().await.is_some() {
-        let n_layers = global_config::get().n_layers;
-        CURRENT_LAYER
-            .fetch_update(Ordering::Release, Ordering::Relaxed, move |c| {
-                Some((c + 1) % n_layers)
-            })
-            .unwrap();
-        if events.send(Event::NewLayer).is_err() {
-            break;
-        }
+        internal_task::change_layer(events, LayerChange::Next);
     }
 }
 
@@ -0,0 +1,35 @@
+use tokio::{
+    io::{self, AsyncBufReadExt as _, AsyncRead, BufReader},
+    process::Command,
+};
+
+/// Shows the lines rendered for somebar as its status, by running `somebar` with `args` and the
+/// line for each of them.
+pub async fn run(lines: impl AsyncRead + Unpin, program: &str, args: &[String]) {
+    let mut lines = BufReader::new(lines).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("failed to read the somebar status: {e}");
+                break;
+            }
+        };
+        if let Err(e) = set_status(program, args, &line).await {
+            log::error!("failed to update the somebar status: {e}");
+        }
+    }
+}
+
+async fn set_status(program: &str, args: &[String], line: &str) -> io::Result<()> {
+    let output = Command::new(program).args(args).arg(line).output().await?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
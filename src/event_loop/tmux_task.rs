@@ -0,0 +1,148 @@
+use crate::{display::tmux::align, model::Alignment};
+use std::{fs, path::Path};
+use tokio::{
+    io::{self, AsyncBufReadExt as _, AsyncRead, BufReader},
+    process::Command,
+};
+
+/// Shows the lines rendered for tmux, either by writing each one to `file` or by setting
+/// tmux's `status-left` and `status-right`. tmux shows its windows in the middle, so in the
+/// latter case the middle blocks go at the start of `status-right`.
+pub async fn run(lines: impl AsyncRead + Unpin, file: Option<&Path>) {
+    let mut lines = BufReader::new(lines).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                log::error!("failed to read the tmux status line: {e}");
+                break;
+            }
+        };
+        let shown = match file {
+            Some(file) => write(file, &line),
+            None => set_status("tmux", &line).await,
+        };
+        if let Err(e) = shown {
+            log::error!("failed to update the tmux status line: {e}");
+        }
+    }
+}
+
+/// Replaces the contents of `file` with `line` in one go, so tmux never reads half of it.
+fn write(file: &Path, line: &str) -> io::Result<()> {
+    let mut tmp = file.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, format!("{line}\n"))?;
+    fs::rename(&tmp, file)
+}
+
+async fn set_status(program: &str, line: &str) -> io::Result<()> {
+    let [left, middle, right] = split(line);
+    let right = match (middle, right) {
+        ("", right) => right.to_owned(),
+        (middle, "") => middle.to_owned(),
+        (middle, right) => format!("{middle} {right}"),
+    };
+    let output = Command::new(program)
+        .args(["set", "-g", "status-left", left, ";"])
+        .args([
+            "set",
+            "-g",
+            "status-left-length",
+            &left.len().to_string(),
+            ";",
+        ])
+        .args(["set", "-g", "status-right", &right, ";"])
+        .args(["set", "-g", "status-right-length", &right.len().to_string()])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// The blocks of each alignment. The markers are found by skipping `##`, which is how the text
+/// of the blocks escapes `#`.
+fn split(line: &str) -> [&str; 3] {
+    let mut parts = [""; 3];
+    let mut current: Option<(Alignment, usize)> = None;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        if rest.starts_with("##") {
+            i += 2;
+            continue;
+        }
+        let marker = [Alignment::Left, Alignment::Middle, Alignment::Right]
+            .into_iter()
+            .find(|a| rest.starts_with(align(*a)));
+        match marker {
+            Some(alignment) => {
+                if let Some((a, start)) = current {
+                    parts[a as usize] = &line[start..i];
+                }
+                i += align(alignment).len();
+                current = Some((alignment, i));
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if let Some((a, start)) = current {
+        parts[a as usize] = &line[start..];
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, os::unix::fs::PermissionsExt as _};
+
+    #[test]
+    fn split_skips_escaped_hashes_next_to_markers() {
+        let line = "#[align=left]a ###[align=centre]b###[align=right]##[align=right]c##";
+        assert_eq!(split(line), ["a ##", "b##", "##[align=right]c##"]);
+    }
+
+    #[test]
+    fn split_with_only_middle_or_only_right_blocks() {
+        assert_eq!(split("#[align=centre]mid"), ["", "mid", ""]);
+        assert_eq!(split("#[align=right]right"), ["", "", "right"]);
+        assert_eq!(split("#[align=left]#[align=right]é"), ["", "", "é"]);
+    }
+
+    #[tokio::test]
+    async fn set_status_sets_both_sides() {
+        let dir = env::temp_dir().join(format!("lemon-tmux-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let args = dir.join("args");
+        let tmux = dir.join("tmux");
+        fs::write(
+            &tmux,
+            format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n", args.display()),
+        )
+        .unwrap();
+        fs::set_permissions(&tmux, fs::Permissions::from_mode(0o755)).unwrap();
+
+        set_status(
+            tmux.to_str().unwrap(),
+            "#[align=left]L#[align=centre]M#[align=right]R",
+        )
+        .await
+        .unwrap();
+
+        let args = fs::read_to_string(&args).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            args.lines().collect::<Vec<_>>().join(" "),
+            "set -g status-left L ; set -g status-left-length 1 ; \
+             set -g status-right M R ; set -g status-right-length 3"
+        );
+    }
+}
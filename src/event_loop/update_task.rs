@@ -1,102 +1,209 @@
-use super::{RunningConfig, current_layer};
+use super::{
+    RunningConfig,
+    bar_task::BarInput,
+    current_layer,
+    replay::{self, Entry},
+};
 use crate::{
-    Config,
-    display::{Bar, display_block},
+    display::Bar,
     global_config,
-    model::{AffectedMonitor, Alignment, block::BlockUpdate},
+    model::{
+        AffectedMonitor, Alignment,
+        block::{BlockId, BlockUpdate},
+    },
 };
 use enum_iterator::IntoEnumIterator;
-use std::sync::Once;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 use tokio::{
     io::AsyncWriteExt as _,
-    process::ChildStdin,
+    select,
     sync::mpsc::{self, Sender, error::SendError},
+    time::{self, Instant},
 };
 
+/// The channel a block sends its updates through. Clones share what the block last sent.
 #[derive(Clone)]
-pub struct UpdateChannel(Sender<BlockUpdate>);
+pub struct UpdateChannel {
+    ch: Sender<BlockUpdate>,
+    shown: Arc<Mutex<HashMap<AffectedMonitor, String>>>,
+}
 
 impl From<Sender<BlockUpdate>> for UpdateChannel {
     fn from(ch: Sender<BlockUpdate>) -> Self {
-        Self(ch)
+        Self {
+            ch,
+            shown: Default::default(),
+        }
     }
 }
 
 impl From<&Sender<BlockUpdate>> for UpdateChannel {
     fn from(ch: &Sender<BlockUpdate>) -> Self {
-        Self(ch.clone())
+        Self::from(ch.clone())
     }
 }
 
 impl UpdateChannel {
     pub async fn send(&self, u: impl Into<BlockUpdate>) -> Result<(), SendError<BlockUpdate>> {
-        self.0.send(u.into()).await
+        let u = u.into();
+        let (_, _, monitor) = u.id();
+        let text = u.text().iter().map(|t| t.text.as_str()).collect::<String>();
+        {
+            let mut shown = self.shown.lock().unwrap();
+            if monitor == AffectedMonitor::All {
+                shown.clear();
+            }
+            shown.insert(monitor, text);
+        }
+        self.ch.send(u).await
+    }
+
+    /// The text last sent for `monitor`.
+    pub fn shown(&self, monitor: u8) -> String {
+        let shown = self.shown.lock().unwrap();
+        shown
+            .get(&AffectedMonitor::Single(monitor))
+            .or_else(|| shown.get(&AffectedMonitor::All))
+            .cloned()
+            .unwrap_or_default()
     }
 }
 
-pub async fn update<B>(
-    config: Config<'static>,
+/// Draws the bars whenever a block is updated. Unless `every_update` is set, which replays use
+/// to show each update they recorded, updates are coalesced into frames and the ones that don't
+/// change anything are dropped.
+pub(super) async fn update<B>(
+    config: &mut RunningConfig,
     mut updates: mpsc::Receiver<BlockUpdate>,
-    lemon_inputs: &mut [ChildStdin],
+    mut bars: mpsc::Receiver<(u8, BarInput)>,
+    mut toggles: mpsc::Receiver<BlockId>,
+    every_update: bool,
 ) where
     B: Bar<String>,
 {
-    let mut config = RunningConfig::from(config);
     let mut line = String::new();
-    while let Some(update) = updates.recv().await {
-        let (_, _, monitor) = update.id();
-        if !config.update(update) {
-            // TODO: we could save an update, but zelbar is bugged and so redundant updates
-            // actually fix it
-            // continue;
-        }
-        match monitor {
-            AffectedMonitor::Single(m) => match lemon_inputs.get_mut(usize::from(m)) {
-                Some(input) => {
-                    line = build_line::<B>(&config, m, line);
-                    log::trace!("{m} => {line}");
-                    if let Err(e) = input.write_all(line.as_bytes()).await {
-                        log::error!("Couldn't talk to lemon bar :( {:?}", e);
+    // both grow as monitors are plugged in.
+    let mut lemon_inputs = Vec::<Option<BarInput>>::new();
+    let mut dirty = Vec::<bool>::new();
+    let mut next_frame = Instant::now();
+    let mut rendered_layer = current_layer();
+    loop {
+        select! {
+            update = updates.recv() => {
+                let Some(update) = update else {
+                    break;
+                };
+                if every_update {
+                    mark_dirty(config, update, &mut dirty, true);
+                } else {
+                    // updates that arrive before the next frame are applied together, so a
+                    // burst of them only redraws each bar once.
+                    time::sleep_until(next_frame).await;
+                    mark_dirty(config, update, &mut dirty, false);
+                    while let Ok(update) = updates.try_recv() {
+                        mark_dirty(config, update, &mut dirty, false);
                     }
                 }
-                None => log::error!("monitor: {m} is out of bounds"),
-            },
-            AffectedMonitor::All => {
-                for (monitor, input) in lemon_inputs.iter_mut().enumerate() {
-                    line = build_line::<B>(&config, monitor as _, line);
-                    log::trace!("{monitor} => {line}");
-                    if let Err(e) = input.write_all(line.as_bytes()).await {
-                        log::error!("Couldn't talk to lemon bar :( {:?}", e);
-                    }
+            }
+            Some((monitor, input)) = bars.recv() => {
+                // a bar that was just (re)started is empty, so it gets the current line right
+                // away.
+                log::debug!("bar {monitor} connected");
+                replay::record(|| Entry::Bar(monitor));
+                config.resize_monitors();
+                let monitor = usize::from(monitor);
+                if lemon_inputs.len() <= monitor {
+                    lemon_inputs.resize_with(monitor + 1, || None);
+                }
+                lemon_inputs[monitor] = Some(input);
+                if dirty.len() <= monitor {
+                    dirty.resize(monitor + 1, false);
                 }
+                dirty[monitor] = true;
+            }
+            Some(bid) = toggles.recv() => {
+                replay::record(|| Entry::Toggle(bid));
+                config.toggle(bid);
+                dirty.fill(true);
             }
         }
+        // which blocks are visible depends on the layer, so every bar has to be redrawn even if
+        // no text changed.
+        if rendered_layer != current_layer() {
+            rendered_layer = current_layer();
+            dirty.fill(true);
+        }
+        for (monitor, input) in lemon_inputs.iter_mut().enumerate() {
+            if !dirty.get_mut(monitor).is_some_and(std::mem::take) {
+                continue;
+            }
+            let Some(stdin) = input else {
+                continue;
+            };
+            line = build_line::<B>(config, monitor as _, line);
+            log::trace!("{monitor} => {line}");
+            if let Err(e) = stdin.write_all(line.as_bytes()).await {
+                log::error!("Couldn't talk to lemon bar :( {:?}", e);
+                // the bar is gone, wait for it to be restarted.
+                *input = None;
+            }
+        }
+        next_frame = Instant::now() + global_config::get().file_config.frame_interval();
     }
 }
 
-fn build_line<B>(config: &RunningConfig, monitor: u8, mut line: String) -> String
+fn mark_dirty(config: &mut RunningConfig, update: BlockUpdate, dirty: &mut [bool], force: bool) {
+    replay::record(|| Entry::Update(update.clone()));
+    let (_, _, monitor) = update.id();
+    // zelbar is bugged and redundant updates actually fix it, so those can be forced.
+    if !config.update(update) && !force && !global_config::get().redundant_updates() {
+        return;
+    }
+    match monitor {
+        // bars that haven't connected yet will be drawn when they do.
+        AffectedMonitor::Single(m) => {
+            if let Some(d) = dirty.get_mut(usize::from(m)) {
+                *d = true;
+            }
+        }
+        AffectedMonitor::All => dirty.fill(true),
+    }
+}
+
+fn build_line<B>(config: &mut RunningConfig, monitor: u8, mut line: String) -> String
 where
     B: Bar<String>,
 {
     line.clear();
     let global_config = global_config::get();
     let current_layer = current_layer();
+    config.invalidate_stale_fragments(current_layer, &global_config);
     let mut bar = B::new(line, global_config.file_config.separator.clone());
-    Alignment::into_enum_iter()
-        .map(|a| (a, &config[a]))
-        .filter(|(_, c)| !c.is_empty())
-        .for_each(|(al, blocks)| {
-            let set_alignment = Once::new();
-            blocks
-                .iter()
-                .enumerate()
-                .filter(|(_, b)| !b.last_run[monitor].is_empty())
-                .filter(|(_, b)| b.block.layer == current_layer)
-                .for_each(|(index, b)| {
-                    set_alignment.call_once(|| bar.set_alignment(al).unwrap());
-                    display_block(&mut bar, &b.block, &b.last_run[monitor], index, monitor).unwrap()
-                });
-        });
+    for al in Alignment::into_enum_iter() {
+        let mut set_alignment = false;
+        config[al]
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, b)| {
+                b.last_run
+                    .get(monitor.into())
+                    .is_some_and(|t| !t.is_empty())
+            })
+            .filter(|(_, b)| b.block.layer == current_layer && !b.hidden)
+            .for_each(|(index, b)| {
+                if !set_alignment {
+                    set_alignment = true;
+                    bar.set_alignment(al).unwrap();
+                }
+                let fragment = b.fragment::<B>(index, monitor);
+                if !fragment.is_empty() {
+                    bar.push_fragment(fragment).unwrap();
+                }
+            });
+    }
     // TODO: line.lemon('O', tray_offset).unwrap();
     let mut line = bar.into_inner();
     line.push('\n');
@@ -0,0 +1,132 @@
+use super::{MouseButton, action_task::Action, events::EventBus};
+use crate::model::block::BlockId;
+use futures::stream;
+use std::{env, future::pending, path::PathBuf};
+use tokio::{
+    fs,
+    io::{self, AsyncBufReadExt as _, AsyncWriteExt as _, BufReader},
+    join,
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+
+fn socket_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// The start of the names of the sockets of the block titled `title`, they end with the pid of
+/// the module. Every bar runs its own module, so there can be several.
+fn socket_prefix(title: &str) -> String {
+    format!("lemon-waybar-{}-", title.replace('/', "_"))
+}
+
+/// Where the waybar module of the block titled `title` run by this process listens for clicks.
+fn socket_path(title: &str) -> PathBuf {
+    let pid = std::process::id();
+    socket_dir().join(format!("{}{pid}.sock", socket_prefix(title)))
+}
+
+/// The sockets of every waybar module of the block titled `title`.
+async fn sockets(title: &str) -> io::Result<Vec<PathBuf>> {
+    let prefix = socket_prefix(title);
+    let mut entries = fs::read_dir(socket_dir()).await?;
+    let mut sockets = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let pid = name
+            .to_str()
+            .and_then(|n| n.strip_prefix(&prefix))
+            .and_then(|n| n.strip_suffix(".sock"));
+        if pid.is_some_and(|pid| !pid.is_empty() && pid.bytes().all(|b| b.is_ascii_digit())) {
+            sockets.push(entry.path());
+        }
+    }
+    Ok(sockets)
+}
+
+/// Clicks the block titled `title` of every running waybar module with `button`.
+pub async fn click(title: &str, button: u8) -> io::Result<()> {
+    let sockets = sockets(title).await?;
+    if sockets.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no waybar module is showing '{title}'"),
+        ));
+    }
+    for path in sockets {
+        match UnixStream::connect(&path).await {
+            Ok(mut socket) => socket.write_all(format!("{button}\n").as_bytes()).await?,
+            // the module that made it is gone.
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                let _ = fs::remove_file(&path).await;
+            }
+            Err(e) => log::error!("can't click {path:?}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Removes the socket once the module stops listening.
+struct Socket(PathBuf);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Listens for the clicks sent with [click] and runs the actions of the block, which is the only
+/// one that runs in a waybar module. Each line sent to the socket is a mouse button.
+pub async fn run(events: &EventBus, block: Option<(&str, BlockId)>) {
+    let Some((title, bid)) = block else {
+        return pending().await;
+    };
+    let path = socket_path(title);
+    // a socket left behind by a process with the same pid would make binding fail.
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("can't listen for clicks on {path:?}: {e}");
+            return pending().await;
+        }
+    };
+    let _socket = Socket(path);
+    let (tx, mut rx) = mpsc::channel(16);
+    let accept = async {
+        loop {
+            let socket = match listener.accept().await {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    log::error!("failed to accept a click: {e}");
+                    continue;
+                }
+            };
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(socket).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let button = line
+                        .trim()
+                        .parse::<u8>()
+                        .map_err(|_| "Invalid mouse button");
+                    match button.and_then(MouseButton::try_from) {
+                        Ok(button) => {
+                            let action = Action::new(bid.0, bid.1, 0, button, None, None);
+                            if tx.send(Ok(action.to_string())).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => log::warn!("ignoring click '{line}': {e}"),
+                    }
+                }
+            });
+        }
+    };
+    join!(
+        accept,
+        super::action_task::run(stream::poll_fn(|cx| rx.poll_recv(cx)), events, false)
+    );
+}
@@ -0,0 +1,82 @@
+use std::{
+    io::pipe,
+    os::fd::{AsRawFd as _, OwnedFd, RawFd},
+};
+use tokio::{
+    io::{self, AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, BufReader},
+    net::unix::pipe::Sender,
+    process::Command,
+};
+
+/// The fds xmobar reads the middle and right blocks from, the left ones go to its stdin.
+pub const FDS: [RawFd; 2] = [3, 4];
+
+/// Gives the xmobar spawned by `command` a pipe on each of [FDS], the returned ends write to them.
+pub fn pipes(command: &mut Command) -> io::Result<[Sender; 2]> {
+    let (middle, middle_writer) = pipe()?;
+    let (right, right_writer) = pipe()?;
+    let readers = [OwnedFd::from(middle), OwnedFd::from(right)];
+    // SAFETY: only async signal safe functions are called between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            // both are moved out of the way first, one of them could already be on one of FDS.
+            let mut high = [-1; 2];
+            for (high, reader) in high.iter_mut().zip(&readers) {
+                *high = libc::fcntl(reader.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 10);
+                if *high < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            for (high, fd) in high.into_iter().zip(FDS) {
+                if libc::dup2(high, fd) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok([
+        Sender::from_owned_fd(middle_writer.into())?,
+        Sender::from_owned_fd(right_writer.into())?,
+    ])
+}
+
+/// Splits the lines rendered for xmobar at their tabs, writing the blocks of each alignment to
+/// the reader that shows them.
+pub async fn run(
+    lines: impl AsyncRead + Unpin,
+    mut left: impl AsyncWrite + Unpin,
+    [mut middle, mut right]: [Sender; 2],
+) -> io::Result<()> {
+    let mut lines = BufReader::new(lines).lines();
+    while let Some(line) = lines.next_line().await? {
+        let [l, m, r] = split(&line);
+        write_line(&mut left, l).await?;
+        write_line(&mut middle, m).await?;
+        write_line(&mut right, r).await?;
+    }
+    Ok(())
+}
+
+/// The blocks of each alignment, the ones that have none are empty so that xmobar clears them.
+fn split(line: &str) -> [&str; 3] {
+    let mut parts = line.splitn(3, '\t');
+    [(); 3].map(|_| parts.next().unwrap_or_default())
+}
+
+async fn write_line(sink: &mut (impl AsyncWrite + Unpin), line: &str) -> io::Result<()> {
+    sink.write_all(format!("{line}\n").as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_alignments_are_empty() {
+        assert_eq!(split("a\tb\tc"), ["a", "b", "c"]);
+        assert_eq!(split("\tb"), ["", "b", ""]);
+        assert_eq!(split("a"), ["a", "", ""]);
+        assert_eq!(split(""), ["", "", ""]);
+    }
+}
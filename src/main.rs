@@ -1,15 +1,19 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use enum_iterator::IntoEnumIterator;
 use env_logger::Env;
 use lemon::{
-    display::{self, Program},
-    event_loop,
+    Config,
+    display::{self, Bar, Dialect, Program},
+    event_loop::{self, Exit, replay},
     global_config::GlobalConfig,
     model::Alignment,
     parsing::parse,
 };
-use std::{env, fs, io, path::PathBuf};
-use tokio::sync::broadcast;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -17,8 +21,31 @@ struct Args {
     /// Path to the config file
     #[arg(short, long)]
     config: Option<PathBuf>,
+    /// Record every block update and event to this file, see `lemon replay`
+    #[arg(long)]
+    record: Option<PathBuf>,
     #[command(flatten)]
     overrides: GlobalConfig,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Writes the lines shown during a recording made with `--record` to stdout again
+    Replay {
+        file: PathBuf,
+        /// How many times faster than it was recorded
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Clicks the block shown by a waybar module, for the module's `on-click` commands
+    Click {
+        /// The title of the block
+        block: String,
+        /// The mouse button, 1 is left, 2 middle and 3 right
+        button: u8,
+    },
 }
 
 // TODO:
@@ -29,7 +56,19 @@ struct Args {
 async fn main() -> io::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
     let args = Args::parse();
-    let input = args
+    if let Some(Command::Click { block, button }) = &args.command {
+        return event_loop::waybar_task::click(block, *button).await;
+    }
+    if args.overrides.cmdline.dialect.is_some() && args.overrides.cmdline.program != Program::Stdout
+    {
+        log::error!("--dialect only applies to --program stdout");
+        std::process::exit(1)
+    }
+    if args.overrides.cmdline.program == Program::Waybar && args.overrides.cmdline.block.is_none() {
+        log::error!("a waybar module needs the --block it shows");
+        std::process::exit(1)
+    }
+    let path = args
         .config
         .ok_or(io::ErrorKind::NotFound)
         .inspect(|_| log::info!("Loading config from command line"))
@@ -53,41 +92,132 @@ async fn main() -> io::Result<()> {
                     path
                 })
         })
-        .map_err(io::Error::from)
-        .and_then(fs::read_to_string)
+        .map_err(io::Error::from)?;
+    let mut input = read_config(&path, None)
         .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "Couldn't find config file"))?;
-    let input = Box::leak(input.into_boxed_str());
-    let blocks = match parse(input, args.overrides) {
+    let mut blocks = match parse(input, args.overrides.clone()) {
         Ok(bs) => bs,
         Err(e) => {
             log::error!("Parse error: {:?}", e);
             std::process::exit(1)
         }
     };
-    log::trace!("Parsed blocks");
-    for al in Alignment::into_enum_iter() {
-        log::trace!("{:?}", al);
-        for b in &blocks[al] {
-            log::trace!("{:?}", b);
+    if let Some(Command::Replay { file, speed }) = &args.command {
+        let (file, speed) = (file.clone(), *speed);
+        if !(speed > 0.0 && speed.is_finite()) {
+            log::error!("the speed of a replay has to be a positive number");
+            std::process::exit(1)
         }
+        return with_bar(Replay {
+            blocks,
+            file,
+            speed,
+        })
+        .await;
     }
-    let (bc_send, mut bc_recv) = broadcast::channel(100);
-    if log::log_enabled!(log::Level::Debug) {
-        tokio::task::spawn(async move {
-            while let Ok(ev) = bc_recv.recv().await {
-                log::debug!("[{:?}] event {:?} broadcasted", chrono::Utc::now(), ev)
-            }
-        });
-    } else {
-        drop(bc_recv);
+    if let Some(file) = &args.record {
+        replay::start_recording(file).await?;
+        log::info!("Recording to {file:?}");
     }
-    match lemon::global_config::get().cmdline.program {
-        Program::Zelbar => {
-            event_loop::start_event_loop::<display::Zelbar<_>>(blocks, bc_send).await
+    loop {
+        log::trace!("Parsed blocks");
+        for al in Alignment::into_enum_iter() {
+            log::trace!("{:?}", al);
+            for b in &blocks[al] {
+                log::trace!("{:?}", b);
+            }
         }
-        Program::Lemonbar => {
-            event_loop::start_event_loop::<display::Lemonbar<_>>(blocks, bc_send).await
+        let (exit, old) = with_bar(Run(blocks)).await;
+        if exit == Exit::Quit {
+            break;
         }
+        log::info!("Reloading config from {path:?}");
+        // parsing replaces the global config, even when it fails halfway through.
+        let previous = lemon::global_config::get();
+        let reloaded = read_config(&path, Some(input))
+            .map_err(|e| format!("{e}"))
+            .and_then(|new| {
+                parse(new, args.overrides.clone())
+                    .map(|bs| (new, bs))
+                    .map_err(|e| format!("{e:?}"))
+            });
+        blocks = match reloaded {
+            Ok((new, bs)) => {
+                input = new;
+                bs
+            }
+            Err(e) => {
+                log::error!("Failed to reload config, keeping the old one: {e}");
+                lemon::global_config::set(Arc::unwrap_or_clone(previous));
+                old
+            }
+        };
     }
     Ok(())
 }
+
+/// Something to do with the bar picked by `--program` and `--dialect`.
+trait WithBar {
+    type Output;
+
+    async fn run<B: Bar<String>>(self) -> Self::Output;
+}
+
+async fn with_bar<F: WithBar>(f: F) -> F::Output {
+    let cmdline = lemon::global_config::get().cmdline.clone();
+    match (cmdline.program, cmdline.dialect.unwrap_or_default()) {
+        (Program::Zelbar, _) | (Program::Stdout, Dialect::Zelbar) => {
+            f.run::<display::Zelbar<_>>().await
+        }
+        (Program::Lemonbar, _) | (Program::Stdout, Dialect::Lemonbar) => {
+            f.run::<display::Lemonbar<_>>().await
+        }
+        (Program::Dzen2, _) => f.run::<display::Dzen2<_>>().await,
+        (Program::Xmobar, _) => f.run::<display::Xmobar<_>>().await,
+        (Program::Stdout, Dialect::Pango) | (Program::Somebar, _) => {
+            f.run::<display::Pango<_>>().await
+        }
+        (Program::Stdout, Dialect::Plain) => f.run::<display::Plain<_>>().await,
+        (Program::I3bar, _) => f.run::<display::I3bar<_>>().await,
+        (Program::Waybar, _) => f.run::<display::Waybar<_>>().await,
+        (Program::Tmux, _) => f.run::<display::Tmux<_>>().await,
+    }
+}
+
+/// Runs the blocks until lemon quits or reloads.
+struct Run(Config<'static>);
+
+impl WithBar for Run {
+    type Output = (Exit, Config<'static>);
+
+    async fn run<B: Bar<String>>(self) -> Self::Output {
+        event_loop::start_event_loop::<B>(self.0).await
+    }
+}
+
+/// Shows the lines of a recording again.
+struct Replay {
+    blocks: Config<'static>,
+    file: PathBuf,
+    speed: f64,
+}
+
+impl WithBar for Replay {
+    type Output = io::Result<()>;
+
+    async fn run<B: Bar<String>>(self) -> Self::Output {
+        replay::replay::<B>(self.blocks, &self.file, self.speed).await
+    }
+}
+
+/// Reads the config at `path`, reusing `current` if the file didn't change.
+///
+/// The blocks borrow from the config for as long as lemon runs, so it's leaked. Only reloads
+/// that find the file changed leak it again, so this is bounded by the number of edits.
+fn read_config(path: &Path, current: Option<&'static str>) -> io::Result<&'static str> {
+    let input = fs::read_to_string(path)?;
+    match current {
+        Some(current) if current == input => Ok(current),
+        _ => Ok(Box::leak(input.into_boxed_str())),
+    }
+}
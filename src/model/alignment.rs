@@ -1,20 +1,32 @@
 use enum_iterator::IntoEnumIterator;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoEnumIterator)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, IntoEnumIterator, Serialize, Deserialize)]
 pub enum Alignment {
     Left = 0,
     Middle = 1,
     Right = 2,
 }
 
-impl From<u8> for Alignment {
-    fn from(x: u8) -> Self {
+impl TryFrom<u8> for Alignment {
+    type Error = &'static str;
+    fn try_from(x: u8) -> Result<Self, Self::Error> {
         use Alignment::*;
-        match x {
+        Ok(match x {
             0 => Left,
             1 => Middle,
             2 => Right,
-            _ => unreachable!(),
+            _ => return Err("Invalid alignment"),
+        })
+    }
+}
+
+impl Alignment {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Alignment::Left => "left",
+            Alignment::Middle => "middle",
+            Alignment::Right => "right",
         }
     }
 }
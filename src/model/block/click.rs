@@ -0,0 +1,221 @@
+use super::{Actions, AfterClick, BlockId, OutputFormat, TaskData};
+use crate::{
+    event_loop::{
+        MouseButton, Segment, current_layer,
+        events::{BlockEvents, Requests},
+        internal_task,
+        update_task::UpdateChannel,
+    },
+    model::{ActiveMonitors, AffectedMonitor},
+    parsing::parser::Title,
+    util::{
+        cmd::{log_failure, run_cmd},
+        trim_new_lines,
+    },
+};
+use std::sync::Arc;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// What happens to a click whose action comes while the previous one is still running.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActionPolicy {
+    /// It runs once the previous ones finished.
+    #[default]
+    Queue,
+    /// It is dropped.
+    DropWhileRunning,
+    /// The previous action is killed.
+    CancelPrevious,
+}
+
+/// Runs the click actions of a block on their own tasks, so that the block keeps updating while
+/// they run. Actions that are still running when the runner is dropped are killed.
+pub struct Runner {
+    context: Arc<Context>,
+    policy: ActionPolicy,
+    queue: Option<mpsc::UnboundedSender<Click>>,
+    running: Option<JoinHandle<()>>,
+}
+
+struct Click {
+    monitor: u8,
+    button: MouseButton,
+    segment: Option<Segment>,
+    count: u32,
+}
+
+/// Everything an action needs from its block.
+struct Context {
+    block_name: Title<'static>,
+    bid: BlockId,
+    actions: Actions<'static>,
+    after_click: [AfterClick<BlockId>; MouseButton::COUNT],
+    updates: UpdateChannel,
+    monitors: ActiveMonitors,
+    output: OutputFormat,
+    requests: Requests,
+}
+
+impl Runner {
+    pub fn new(data: &TaskData, events: &BlockEvents) -> Self {
+        let context = Arc::new(Context {
+            block_name: data.block_name,
+            bid: data.bid,
+            actions: data.actions,
+            after_click: data.after_click.clone(),
+            updates: data.updates.clone(),
+            monitors: data.monitors,
+            output: data.output,
+            requests: events.requests(),
+        });
+        let (queue, running) = match data.action_policy {
+            ActionPolicy::Queue => {
+                let (tx, mut rx) = mpsc::unbounded_channel::<Click>();
+                let context = context.clone();
+                let worker = tokio::spawn(async move {
+                    while let Some(click) = rx.recv().await {
+                        context.run(click).await;
+                    }
+                });
+                (Some(tx), Some(worker))
+            }
+            ActionPolicy::DropWhileRunning | ActionPolicy::CancelPrevious => (None, None),
+        };
+        Self {
+            context,
+            policy: data.action_policy,
+            queue,
+            running,
+        }
+    }
+
+    /// Runs the action bound to `button`, if there is one, according to the block's
+    /// [ActionPolicy].
+    pub fn click(
+        &mut self,
+        monitor: u8,
+        button: MouseButton,
+        segment: Option<Segment>,
+        count: u32,
+    ) {
+        if self.context.actions[button].is_none() {
+            return;
+        }
+        let click = Click {
+            monitor,
+            button,
+            segment,
+            count,
+        };
+        if let Some(queue) = &self.queue {
+            let _ = queue.send(click);
+            return;
+        }
+        if let Some(running) = self.running.take_if(|r| !r.is_finished()) {
+            match self.policy {
+                ActionPolicy::DropWhileRunning => {
+                    log::debug!(
+                        "'{}' action still running, dropping click",
+                        self.context.block_name
+                    );
+                    self.running = Some(running);
+                    return;
+                }
+                // the command is killed along with the future that was running it.
+                _ => running.abort(),
+            }
+        }
+        let context = self.context.clone();
+        self.running = Some(tokio::spawn(async move { context.run(click).await }));
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        if let Some(running) = &self.running {
+            running.abort();
+        }
+    }
+}
+
+impl Context {
+    /// Runs the command bound to the clicked button and does whatever the block asked for once
+    /// it finished.
+    ///
+    /// Commands starting with [internal_task::PREFIX] are handed to the event loop instead.
+    ///
+    /// Besides the [block's variables](super::env_vars) the command gets:
+    /// - `LEMON_BUTTON`: the number of the mouse button
+    /// - `LEMON_BLOCK_TEXT`: the text the block currently shows on the clicked monitor
+    /// - `LEMON_SEGMENT`: the segment of the block that was clicked, empty if it has none
+    /// - `LEMON_SEGMENT_INDEX`: the position of the clicked text among the texts of the block,
+    ///   starting at 0, empty if the bar can't tell
+    /// - `LEMON_CLICKS`: how many clicks the action runs for, see [ClickLimit](super::ClickLimit)
+    async fn run(&self, click: Click) {
+        let Click {
+            monitor,
+            button,
+            segment,
+            count,
+        } = click;
+        let Some(action) = self.actions[button] else {
+            return;
+        };
+        let after = &self.after_click[button as usize - 1];
+        if let Some(internal) = action.strip_prefix(internal_task::PREFIX) {
+            match internal.parse() {
+                Ok(internal) => self.requests.internal(internal),
+                Err(e) => log::error!("invalid internal action '{action}': {e}"),
+            }
+        } else {
+            let mut env = super::env_vars(self.block_name, self.bid, monitor.into()).to_vec();
+            env.extend([
+                ("LEMON_BUTTON", button.to_string()),
+                ("LEMON_BLOCK_TEXT", self.updates.shown(monitor)),
+                (
+                    "LEMON_SEGMENT_INDEX",
+                    segment
+                        .as_ref()
+                        .map(|s| s.index.to_string())
+                        .unwrap_or_default(),
+                ),
+                (
+                    "LEMON_SEGMENT",
+                    segment.and_then(|s| s.id).unwrap_or_default(),
+                ),
+                ("LEMON_CLICKS", count.to_string()),
+            ]);
+            let output = run_cmd(
+                self.block_name.title,
+                action,
+                monitor.into(),
+                current_layer(),
+                // actions can start programs that outlive them, like a volume mixer.
+                None,
+                &env,
+            )
+            .await;
+            match output {
+                Ok(mut output) => {
+                    trim_new_lines(&mut output);
+                    log::debug!("'{}' action printed: {output}", self.block_name);
+                    if after.show_output {
+                        let monitor = match self.monitors {
+                            ActiveMonitors::All => AffectedMonitor::All,
+                            ActiveMonitors::PerMonitor => AffectedMonitor::Single(monitor),
+                        };
+                        let text = self.output.read(self.block_name, output);
+                        let _ = self.updates.send((text, self.bid, monitor)).await;
+                    }
+                }
+                Err(e) => log_failure(format_args!("'{}' action", self.block_name), &e),
+            }
+        }
+        if after.refresh {
+            self.requests.refresh(self.bid);
+        }
+        for bid in &after.refresh_others {
+            self.requests.refresh(*bid);
+        }
+    }
+}
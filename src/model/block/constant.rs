@@ -1,40 +1,34 @@
-use super::{Event, TaskData};
-use crate::{event_loop::current_layer, util::cmd::run_cmd};
+use super::{TaskData, click};
+use crate::event_loop::{Event, events::BlockEvents};
 use futures::{FutureExt, future::BoxFuture};
-use tokio::sync::broadcast;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Static;
 
 impl super::BlockTask for Static {
-    fn start(&self, events: broadcast::Receiver<Event>, data: TaskData) -> BoxFuture<'static, ()> {
+    fn start(&self, events: BlockEvents, data: TaskData) -> BoxFuture<'static, ()> {
         start(events, data).boxed()
     }
 }
 
-async fn start(
-    mut events: broadcast::Receiver<Event>,
-    TaskData {
-        block_name,
-        cmd,
-        monitors,
-        bid,
-        updates,
-        actions,
-        ..
-    }: TaskData,
-) {
-    for mon in monitors.iter() {
-        let _ = updates.send((cmd.to_owned(), bid, mon)).await;
-    }
-    while let Ok(e) = events.recv().await {
+async fn start(mut events: BlockEvents, data: TaskData) {
+    let show = || async {
+        for mon in data.monitors.iter() {
+            let _ = data
+                .updates
+                .send((data.cmd.to_owned(), data.bid, mon))
+                .await;
+        }
+    };
+    let mut clicks = click::Runner::new(&data, &events);
+    show().await;
+    while let Some(e) = events.recv().await {
         match e {
-            Event::MouseClicked(id, mon, button) if id == bid => {
-                if let Some(a) = actions[button] {
-                    let _ = run_cmd(block_name.title, a, mon.into(), current_layer()).await;
-                }
+            Event::MouseClicked(_, mon, button, segment, count) => {
+                clicks.click(mon, button, segment, count);
             }
-            Event::Signal | Event::NewLayer | Event::MouseClicked(..) => {}
+            Event::NewMonitors | Event::Refresh(_) => show().await,
+            Event::Signal | Event::NewLayer => {}
         }
     }
 }
@@ -1,27 +1,34 @@
+pub mod click;
 pub mod constant;
 pub mod native;
 pub mod persistent;
+pub mod segments;
 pub mod signal_task;
 pub mod timed;
 
 use super::{ActivationLayer, ActiveMonitors, AffectedMonitor, Alignment, Color};
 use crate::{
-    event_loop::{Event, MouseButton, update_task::UpdateChannel},
+    event_loop::{MouseButton, events::BlockEvents, outputs, update_task::UpdateChannel},
     parsing::parser::Title,
 };
 use derive_builder::Builder;
 use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     convert::TryFrom,
     ops::{Index, IndexMut},
     os::raw::c_int,
+    time::Duration,
 };
-use tokio::sync::broadcast;
-
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct BlockText {
     pub decorations: TextDecorations,
     pub text: String,
+    /// Identifies this segment of the block in the clicks it receives.
+    pub segment: Option<String>,
+    /// The index of the font of this segment, instead of the one of the block.
+    pub font: Option<String>,
 }
 
 impl From<String> for BlockText {
@@ -29,6 +36,8 @@ impl From<String> for BlockText {
         Self {
             decorations: Default::default(),
             text,
+            segment: None,
+            font: None,
         }
     }
 }
@@ -39,7 +48,7 @@ impl BlockText {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockUpdate {
     text: Vec<BlockText>,
     alignment: Alignment,
@@ -52,10 +61,7 @@ pub type BlockId = (Alignment, usize);
 impl<M: Into<AffectedMonitor>> From<(String, BlockId, M)> for BlockUpdate {
     fn from((text, (alignment, index), monitor): (String, BlockId, M)) -> Self {
         Self {
-            text: vec![BlockText {
-                decorations: Default::default(),
-                text,
-            }],
+            text: vec![BlockText::from(text)],
             alignment,
             index,
             monitor: monitor.into(),
@@ -75,6 +81,10 @@ impl<M: Into<AffectedMonitor>> From<(Vec<BlockText>, BlockId, M)> for BlockUpdat
 }
 
 impl BlockUpdate {
+    pub fn text(&self) -> &[BlockText] {
+        &self.text
+    }
+
     pub fn into_inner_text(self) -> Vec<BlockText> {
         self.text
     }
@@ -110,13 +120,74 @@ pub struct TaskData {
     pub monitors: ActiveMonitors,
     pub signal: Signal,
     pub precondition: Option<Precondition<'static>>,
+    pub timeout: Option<Duration>,
+    pub timeout_marker: &'static str,
+    pub on_error: OnError<'static>,
+    pub error_color: Option<Color>,
+    pub retries: u32,
+    pub after_click: [AfterClick<BlockId>; MouseButton::COUNT],
+    pub action_policy: click::ActionPolicy,
+    pub output: OutputFormat,
+}
+
+/// The variables that tell a block's commands which block they run for, on top of the ones from
+/// [`GlobalConfig::as_env_vars`](crate::model::global_config::GlobalConfig::as_env_vars):
+/// - `LEMON_BLOCK`: the title of the block
+/// - `LEMON_ALIGNMENT`: `left`, `middle` or `right`
+/// - `LEMON_OUTPUT`: the output of the bar, empty if it wasn't given one
+///
+/// Click actions get a few more, see [click::Runner].
+pub fn env_vars(
+    block_name: Title<'_>,
+    (alignment, _): BlockId,
+    monitor: AffectedMonitor,
+) -> [(&'static str, String); 3] {
+    [
+        ("LEMON_BLOCK", block_name.title.to_owned()),
+        ("LEMON_ALIGNMENT", alignment.as_str().to_owned()),
+        ("LEMON_OUTPUT", outputs::name_of(monitor)),
+    ]
 }
 
 pub trait BlockTask: std::fmt::Debug {
-    fn start(&self, events: broadcast::Receiver<Event>, data: TaskData) -> BoxFuture<'static, ()>;
+    fn start(&self, events: BlockEvents, data: TaskData) -> BoxFuture<'static, ()>;
 }
 
-pub type Actions<'s> = [Option<&'s str>; 5];
+pub type Actions<'s> = [Option<&'s str>; MouseButton::COUNT];
+
+/// What happens once a click action finished, besides whatever its command did. `B` identifies
+/// other blocks, by title while parsing and by id once the blocks are running.
+#[derive(Clone, Debug)]
+pub struct AfterClick<B> {
+    /// The output of the action replaces the text of the block.
+    pub show_output: bool,
+    /// The block runs its command again.
+    pub refresh: bool,
+    /// Other blocks that run their command again.
+    pub refresh_others: Vec<B>,
+}
+
+/// How often a click action can run when its button is pressed in quick succession.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClickLimit {
+    /// Clicks that come this soon after the action ran are dropped.
+    pub throttle: Option<Duration>,
+    /// The action only runs once the button wasn't pressed for this long.
+    pub debounce: Option<Duration>,
+    /// Instead of being dropped, the clicks that were held back run the action once, which gets
+    /// their number in `LEMON_CLICKS`.
+    pub accumulate: bool,
+}
+
+impl<B> Default for AfterClick<B> {
+    fn default() -> Self {
+        Self {
+            show_output: false,
+            refresh: false,
+            refresh_others: Vec::new(),
+        }
+    }
+}
 
 impl<'s> Index<MouseButton> for Actions<'s> {
     type Output = Option<&'s str>;
@@ -159,7 +230,7 @@ impl<'a> TryFrom<&'a str> for Offset<'a> {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct TextDecorations {
     pub bg: Option<Color>,
     pub fg: Option<Color>,
@@ -190,6 +261,40 @@ impl Precondition<'_> {
     }
 }
 
+/// How the output of the commands of a block is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// As the text of the block.
+    #[default]
+    Text,
+    /// As lines of json arrays of segments, see [segments::parse].
+    Json,
+}
+
+impl OutputFormat {
+    /// Turns what a command of `block_name` printed into the text of the block.
+    pub fn read(self, block_name: Title<'_>, output: String) -> Vec<BlockText> {
+        match self {
+            Self::Text => vec![BlockText::from(output)],
+            Self::Json => segments::parse(block_name, &output),
+        }
+    }
+}
+
+/// What a block shows when its command fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnError<'a> {
+    /// Whatever the command printed before failing.
+    #[default]
+    Output,
+    /// The last output of a successful run.
+    Keep,
+    /// Some fixed text.
+    Text(&'a str),
+    /// Nothing at all.
+    Hide,
+}
+
 #[derive(Builder, Debug)]
 #[builder(setter(strip_option), build_fn(skip, name = "build"))]
 pub struct Block<'a> {
@@ -202,6 +307,8 @@ pub struct Block<'a> {
     #[builder(default)]
     pub raw: bool,
     #[builder(default)]
+    pub output: OutputFormat,
+    #[builder(default)]
     pub layer: ActivationLayer,
     #[builder(default)]
     pub active_in: ActiveMonitors,
@@ -209,6 +316,26 @@ pub struct Block<'a> {
     pub signal: Signal,
     #[builder(default)]
     pub precondition: Option<Precondition<'a>>,
+    #[builder(default)]
+    pub timeout: Option<Duration>,
+    /// What to show when the command times out
+    #[builder(default)]
+    pub timeout_marker: &'a str,
+    #[builder(default)]
+    pub on_error: OnError<'a>,
+    /// The foreground color of the text shown when the command fails
+    #[builder(default)]
+    pub error_color: Option<Color>,
+    /// How many times a failed command is retried before giving up
+    #[builder(default)]
+    pub retries: u32,
+    #[builder(default)]
+    pub after_click: [AfterClick<&'a str>; MouseButton::COUNT],
+    #[builder(default)]
+    pub click_limits: [ClickLimit; MouseButton::COUNT],
+    /// What happens to clicks that come while an action is running
+    #[builder(default)]
+    pub action_policy: click::ActionPolicy,
 
     // mandatory
     #[builder(setter(skip), default)] // custom setter is just not providing one
@@ -216,22 +343,58 @@ pub struct Block<'a> {
     #[builder(setter(skip), default)]
     pub alignment: Alignment,
     #[builder(setter(skip), default)]
-    pub available_actions: [Option<&'a str>; 5],
+    pub available_actions: Actions<'a>,
     #[builder(setter(skip), default)]
     pub cmd: &'a str,
     #[builder(setter(skip), default)]
     pub task: Box<dyn BlockTask>,
 }
 
+impl Block<'_> {
+    /// The buttons the bar has to report, the ones with an action.
+    pub fn buttons(&self) -> AvailableActions {
+        AvailableActions::from(self.available_actions.map(|o| o.is_some()))
+    }
+}
+
 impl Block<'static> {
+    pub fn title(&self) -> Title<'static> {
+        self.title
+    }
+
+    /// The buttons whose double click has an action.
+    pub fn double_clicks(&self) -> impl Iterator<Item = MouseButton> + '_ {
+        AvailableActions::from(self.available_actions.map(|o| o.is_some()))
+            .iter()
+            .filter(|b| b.pressed() != *b)
+    }
+
+    /// Starts the block, `ids` maps the titles of every block to their id.
     pub fn start(
         &self,
         block_id: BlockId,
-        broadcast: broadcast::Receiver<Event>,
+        mut events: BlockEvents,
         updates: UpdateChannel,
+        ids: &HashMap<&str, BlockId>,
     ) -> BoxFuture<'static, ()> {
+        events.limit_clicks(self.click_limits);
+        let after_click = self.after_click.clone().map(|a| AfterClick {
+            show_output: a.show_output,
+            refresh: a.refresh,
+            refresh_others: a
+                .refresh_others
+                .into_iter()
+                .filter_map(|title| {
+                    let id = ids.get(title).copied();
+                    if id.is_none() {
+                        log::warn!("'{}' can't refresh unknown block '{title}'", self.title);
+                    }
+                    id
+                })
+                .collect(),
+        });
         self.task.start(
-            broadcast,
+            events,
             TaskData {
                 block_name: self.title,
                 cmd: self.cmd,
@@ -242,6 +405,14 @@ impl Block<'static> {
                 monitors: self.active_in,
                 signal: self.signal,
                 precondition: self.precondition,
+                timeout: self.timeout,
+                timeout_marker: self.timeout_marker,
+                on_error: self.on_error,
+                error_color: self.error_color,
+                retries: self.retries,
+                after_click,
+                action_policy: self.action_policy,
+                output: self.output,
             },
         )
     }
@@ -256,7 +427,7 @@ impl<'a> BlockBuilder<'a> {
         title: Title<'a>,
         cmd: &'a str,
         alignment: Alignment,
-        available_actions: [Option<&'a str>; 5],
+        available_actions: Actions<'a>,
         task: Box<dyn BlockTask>,
     ) -> Block<'a> {
         Block {
@@ -266,6 +437,7 @@ impl<'a> BlockBuilder<'a> {
             font: self.font.unwrap_or_default(),
             offset: self.offset.unwrap_or_default(),
             raw: self.raw.unwrap_or_default(),
+            output: self.output.unwrap_or_default(),
             layer: self.layer.unwrap_or_default(),
             alignment,
             active_in: self.active_in.unwrap_or_default(),
@@ -273,17 +445,25 @@ impl<'a> BlockBuilder<'a> {
             task,
             signal: self.signal.unwrap_or(Signal::None),
             precondition: self.precondition.unwrap_or_default(),
+            timeout: self.timeout.unwrap_or_default(),
+            timeout_marker: self.timeout_marker.unwrap_or("timeout"),
+            on_error: self.on_error.unwrap_or_default(),
+            error_color: self.error_color.unwrap_or_default(),
+            retries: self.retries.unwrap_or_default(),
+            after_click: self.after_click.unwrap_or_default(),
+            click_limits: self.click_limits.unwrap_or_default(),
+            action_policy: self.action_policy.unwrap_or_default(),
         }
     }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
-pub struct AvailableActions(u8);
+pub struct AvailableActions(u16);
 
 impl AvailableActions {
     pub fn set(&mut self, index: u8) {
-        debug_assert!(index < 6);
-        self.0 |= 1u8 << index;
+        debug_assert!(usize::from(index) < MouseButton::COUNT);
+        self.0 |= 1u16 << index;
     }
 
     pub fn set_all<I: Iterator<Item = bool>>(&mut self, i: I) {
@@ -294,16 +474,25 @@ impl AvailableActions {
         }
     }
 
+    /// The buttons the bar has to report for these actions, double clicks are reported as the
+    /// presses of their button.
+    pub fn pressed(self) -> Self {
+        let mut pressed = Self::default();
+        for b in self.iter() {
+            pressed.set(b.pressed() as u8 - 1);
+        }
+        pressed
+    }
+
     pub fn iter(self) -> impl Iterator<Item = MouseButton> {
-        (0..5)
+        (0..MouseButton::COUNT as u8)
             .filter(move |i| self.0 & (1 << i) != 0)
-            .map(|i| i + 1)
-            .map(Into::into)
+            .filter_map(|i| MouseButton::try_from(i + 1).ok())
     }
 }
 
-impl From<[bool; 5]> for AvailableActions {
-    fn from(value: [bool; 5]) -> Self {
+impl From<[bool; MouseButton::COUNT]> for AvailableActions {
+    fn from(value: [bool; MouseButton::COUNT]) -> Self {
         let mut s = Self::default();
         s.set_all(value.into_iter());
         s
@@ -1,10 +1,10 @@
 use std::time::Duration;
 
 use chrono::{Timelike, offset::Local};
-use tokio::{sync::broadcast::Receiver, time::timeout};
+use tokio::time::timeout;
 
 use crate::{
-    event_loop::{Event, current_layer},
+    event_loop::{Event, current_layer, events::BlockEvents},
     model::{
         AffectedMonitor,
         block::{BlockTask, TaskData},
@@ -15,7 +15,7 @@ use futures::{FutureExt, future::BoxFuture};
 #[derive(Debug, Clone, Copy)]
 pub struct Clock;
 
-async fn start(mut events: Receiver<Event>, TaskData { updates, bid, .. }: TaskData) {
+async fn start(mut events: BlockEvents, TaskData { updates, bid, .. }: TaskData) {
     loop {
         let layer = current_layer();
         let out = Local::now()
@@ -33,16 +33,16 @@ async fn start(mut events: Receiver<Event>, TaskData { updates, bid, .. }: TaskD
             log::info!("clock shutting down")
         }
         match timeout(dur_to_next_tick(layer), events.recv()).await {
-            Ok(Ok(Event::NewLayer)) => {}
-            Ok(Ok(_)) => continue,
-            Ok(Err(_)) => return,
+            Ok(Some(Event::NewLayer)) => {}
+            Ok(Some(_)) => continue,
+            Ok(None) => return,
             Err(_) => {}
         }
     }
 }
 
 impl BlockTask for Clock {
-    fn start(&self, events: Receiver<Event>, td: TaskData) -> BoxFuture<'static, ()> {
+    fn start(&self, events: BlockEvents, td: TaskData) -> BoxFuture<'static, ()> {
         start(events, td).boxed()
     }
 }
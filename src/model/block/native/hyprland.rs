@@ -14,13 +14,14 @@ use hyprland::{
     },
     shared::{Address, HyprData, HyprDataActive, WorkspaceId, WorkspaceType},
 };
-use tokio::sync::{Mutex, broadcast, oneshot};
+use tokio::sync::{Mutex, oneshot};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    event_loop::{Event, update_task::UpdateChannel},
+    event_loop::{events::BlockEvents, update_task::UpdateChannel},
     global_config,
     model::{
-        Color,
+        ActiveMonitors, Color,
         block::{BlockId, BlockTask, TaskData, TextDecorations},
     },
 };
@@ -31,19 +32,41 @@ pub(crate) type VoidFuture = BoxFuture<'static, ()>;
 pub struct HyprLand;
 
 impl BlockTask for HyprLand {
-    fn start(&self, events: broadcast::Receiver<Event>, td: TaskData) -> BoxFuture<'static, ()> {
+    fn start(&self, events: BlockEvents, td: TaskData) -> BoxFuture<'static, ()> {
         start(events, td).boxed()
     }
 }
 
 async fn start(
-    mut events: broadcast::Receiver<Event>,
+    events: BlockEvents,
     TaskData {
         updates,
         bid,
         monitors,
         ..
     }: TaskData,
+) {
+    let mut monitors_changed = events.monitors();
+    // the workspaces are tracked per monitor, so everything is rebuilt when a monitor is plugged
+    // in or out.
+    loop {
+        tokio::select! {
+            _ = run(events.shutdown(), updates.clone(), bid, monitors) => return,
+            r = monitors_changed.changed() => {
+                if r.is_err() {
+                    return;
+                }
+                log::info!("monitors changed, restarting hyprland module");
+            }
+        }
+    }
+}
+
+async fn run(
+    shutdown: CancellationToken,
+    updates: UpdateChannel,
+    bid: BlockId,
+    monitors: ActiveMonitors,
 ) {
     let hypr_monitors = loop {
         match hyprland::data::Monitors::get_async().await {
@@ -300,13 +323,12 @@ async fn start(
         },
     ));
 
-    let cancel = async { while events.recv().await.is_ok() {} };
     tokio::select! {
         e = cancelations.next() => {
             log::error!("hyprland module shutting down: {e:?}");
         }
-        _ = cancel => {
-            log::error!("hyprland module shutting down: no more events comming");
+        _ = shutdown.cancelled() => {
+            log::info!("hyprland module shutting down: lemon is exiting");
         }
         r = listener.start_listener_async() => {
             if let Err(e) = r {
@@ -584,6 +606,8 @@ impl Monitor {
                 crate::model::block::BlockText {
                     decorations,
                     text: format!(" {} ", &w.name),
+                    segment: Some(w.name.clone()),
+                    font: None,
                 }
             })
             .collect();
@@ -1,6 +1,6 @@
-use super::super::{BlockId, BlockTask, TaskData};
+use super::super::{BlockTask, TaskData};
 use crate::{
-    event_loop::{Event, MouseButton, current_layer},
+    event_loop::{Event, MouseButton, current_layer, events::BlockEvents},
     model::{
         AffectedMonitor, Color,
         block::{BlockText, TextDecorations},
@@ -12,28 +12,25 @@ use mlib::players::{
     event::{OwnedLibMpvEvent, PlayerEvent},
 };
 use std::{pin::pin, sync::Arc};
-use tokio::{
-    select,
-    sync::{broadcast, watch},
-};
+use tokio::{select, sync::watch};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Music;
 
 #[async_trait::async_trait]
 impl BlockTask for Music {
-    fn start(&self, events: broadcast::Receiver<Event>, td: TaskData) -> BoxFuture<'static, ()> {
+    fn start(&self, events: BlockEvents, td: TaskData) -> BoxFuture<'static, ()> {
         start(events, td).boxed()
     }
 }
 
-async fn start(events: broadcast::Receiver<Event>, TaskData { updates, bid, .. }: TaskData) {
+async fn start(events: BlockEvents, TaskData { updates, bid, .. }: TaskData) {
     players::wait_for_music_daemon_to_start().await;
     let (bar_data, _) = watch::channel(BarData::fetch().await.unwrap());
     let mut receiver = bar_data.subscribe();
     bar_data.send_modify(|_| {});
     let mut bar_data = Arc::new(bar_data);
-    let user_event_loop = pin!(user_event_loop(events, bid, bar_data.clone()));
+    let user_event_loop = pin!(user_event_loop(events, bar_data.clone()));
     let player_event_loop = pin!(async move {
         loop {
             bar_data = player_event_loop(bar_data).await;
@@ -68,27 +65,31 @@ async fn start(events: broadcast::Receiver<Event>, TaskData { updates, bid, .. }
 
 type BarDataWatcher = Arc<watch::Sender<Option<BarData>>>;
 
-async fn user_event_loop(
-    mut ui_events: broadcast::Receiver<Event>,
-    bid: BlockId,
-    bar_data: BarDataWatcher,
-) {
-    while let Ok(ev) = ui_events.recv().await {
+async fn user_event_loop(mut ui_events: BlockEvents, bar_data: BarDataWatcher) {
+    while let Some(ev) = ui_events.recv().await {
         match ev {
-            Event::MouseClicked(id, _, button) if id == bid => {
+            Event::MouseClicked(_, _, button, _, _) => {
                 let e = match button {
                     MouseButton::ScrollUp => players::change_volume(2).await,
                     MouseButton::ScrollDown => players::change_volume(-2).await,
                     MouseButton::Middle => players::cycle_pause().await,
                     MouseButton::Left => players::change_file(players::Direction::Prev).await,
                     MouseButton::Right => players::change_file(players::Direction::Next).await,
+                    MouseButton::ScrollLeft
+                    | MouseButton::ScrollRight
+                    | MouseButton::Back
+                    | MouseButton::Forward
+                    | MouseButton::DoubleLeft
+                    | MouseButton::DoubleMiddle
+                    | MouseButton::DoubleRight => continue,
                 };
                 if let Err(e) = e {
                     log::error!("error pressing {button:?}: {e:?}");
                 }
             }
-            Event::MouseClicked(..) | Event::Signal => {}
+            Event::Signal | Event::NewMonitors => {}
             Event::NewLayer => bar_data.send_modify(|_| {}),
+            Event::Refresh(_) => reset_data(&bar_data).await,
         }
     }
 }
@@ -343,18 +344,26 @@ impl Title {
                     BlockText {
                         decorations: blue,
                         text: "Video:".into(),
+                        segment: None,
+                        font: None,
                     },
                     BlockText {
                         decorations: Default::default(),
                         text: format!(" {v}{el} "),
+                        segment: None,
+                        font: None,
                     },
                     BlockText {
                         decorations: blue,
                         text: "Song:".into(),
+                        segment: None,
+                        font: None,
                     },
                     BlockText {
                         decorations: Default::default(),
                         text: format!(" {c}{el1} "),
+                        segment: None,
+                        font: None,
                     },
                 ]);
             }
@@ -406,18 +415,24 @@ impl BarData {
         blocks.push(BlockText {
             decorations: Default::default(),
             text: format!("[{}] ", self.player_index),
+            segment: None,
+            font: None,
         });
         self.title.to_decorated_text(&mut blocks);
         if let Some(paused) = self.paused {
             blocks.push(BlockText {
                 decorations: Default::default(),
                 text: if paused { " || " } else { " > " }.into(),
+                segment: None,
+                font: None,
             })
         }
         if let Some(volume) = self.volume {
             blocks.push(BlockText {
                 decorations: Default::default(),
                 text: format!("{volume}%"),
+                segment: None,
+                font: None,
             })
         }
         blocks
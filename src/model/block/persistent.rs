@@ -1,98 +1,209 @@
-use super::{Event, TaskData};
+use super::{BlockId, TaskData, click};
 use crate::{
-    event_loop::current_layer,
+    event_loop::{Event, current_layer, events::BlockEvents},
     global_config,
     model::{ActivationLayer, AffectedMonitor},
     parsing::parser::Title,
     util::{
-        cmd::{self, child_debug_loop},
+        cmd::{StderrTail, child_debug_loop},
         trim_new_lines,
     },
 };
 use futures::{
     FutureExt,
     future::BoxFuture,
-    stream::{self, Stream, StreamExt},
+    stream::{FuturesUnordered, Stream, StreamExt},
+};
+use std::{
+    process::{ExitStatus, Stdio},
+    time::Duration,
 };
-use std::{process::Stdio, time::Duration};
 use tokio::{
     io::{self, AsyncBufReadExt, BufReader},
     process::{Child, Command},
-    sync::broadcast,
-    time::timeout,
+    select,
+    time::{self, Instant, timeout},
 };
 use tokio_stream::wrappers::LinesStream;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Always,
+    #[default]
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    fn should_restart(self, status: &io::Result<ExitStatus>) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnFailure => !matches!(status, Ok(s) if s.success()),
+            Self::Never => false,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
-pub struct Persistent;
+pub struct Persistent {
+    pub restart: RestartPolicy,
+    /// The longest we wait before restarting the command, the delay starts at one second and
+    /// doubles every time the command dies in a row.
+    pub max_restart_delay: Duration,
+    /// What to show while the command is not running.
+    pub placeholder: Option<&'static str>,
+}
+
+impl Persistent {
+    const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+    pub const DEFAULT_MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+    /// A command that runs for this long is considered healthy and the delay is reset.
+    const HEALTHY_AFTER: Duration = Duration::from_secs(60);
+}
 
 impl super::BlockTask for Persistent {
-    fn start(&self, events: broadcast::Receiver<Event>, data: TaskData) -> BoxFuture<'static, ()> {
-        start(events, data).boxed()
+    fn start(&self, events: BlockEvents, data: TaskData) -> BoxFuture<'static, ()> {
+        start(*self, events, data).boxed()
     }
 }
 
-async fn start(events: broadcast::Receiver<Event>, data: TaskData) {
+async fn start(persistent: Persistent, mut events: BlockEvents, data: TaskData) {
+    let monitors = data.monitors;
+    let shutdown = events.shutdown();
+    let mut monitors_changed = events.monitors();
+    // the commands are restarted whenever a monitor is plugged in or out, since they get told
+    // which monitor they are running on.
+    let outputs = async {
+        loop {
+            let generation = shutdown.child_token();
+            let mut running = monitors
+                .iter()
+                .map(|mon| supervise(persistent, &data, mon, generation.clone()))
+                .collect::<FuturesUnordered<_>>()
+                .collect::<()>();
+            select! {
+                _ = &mut running => {
+                    // every command gave up, wait for the monitors to change.
+                    select! {
+                        r = monitors_changed.changed() => if r.is_err() { return },
+                        _ = shutdown.cancelled() => return,
+                    }
+                }
+                r = monitors_changed.changed() => {
+                    generation.cancel();
+                    running.await;
+                    if r.is_err() {
+                        return;
+                    }
+                }
+            }
+            if shutdown.is_cancelled() {
+                return;
+            }
+        }
+    };
+    let clicks = async {
+        let mut clicks = click::Runner::new(&data, &events);
+        while let Some(e) = events.recv().await {
+            match e {
+                Event::MouseClicked(_, mon, button, segment, count) => {
+                    clicks.click(mon, button, segment, count);
+                }
+                Event::Signal | Event::NewLayer | Event::NewMonitors | Event::Refresh(_) => (),
+            }
+        }
+    };
+    tokio::join!(outputs, clicks);
+}
+
+/// Runs the command for one monitor, restarting it according to the block's [RestartPolicy].
+async fn supervise(
+    persistent: Persistent,
+    data: &TaskData,
+    mon: AffectedMonitor,
+    shutdown: CancellationToken,
+) {
     let TaskData {
         block_name,
         cmd,
-        updates,
-        actions,
         bid,
-        monitors,
+        ref updates,
         ..
-    } = data;
-    stream::iter(monitors.iter())
-        .for_each_concurrent(monitors.len().get(), |mon| {
-            let updates = updates.clone();
-            let mut events = events.resubscribe();
-            async move {
-                let mut output = match ChildStream::start(block_name, cmd, mon, current_layer()).await {
-                    Ok(o) => o,
-                    Err(e) => {
-                        return log::error!(
-                            "Failed to start persistent command: '{}', because '{:?}'",
-                            cmd,
-                            e
-                        )
-                    }
-                };
+    } = *data;
+    let mut delay = Persistent::MIN_RESTART_DELAY;
+    loop {
+        let started = Instant::now();
+        let status = match ChildStream::start(block_name, cmd, bid, mon, current_layer()).await {
+            Ok(mut output) => {
                 loop {
                     tokio::select! {
-                        Some(l) = output.next() => {
-                            if let Ok(mut l) = l {
+                        l = output.next() => match l {
+                            Some(Ok(mut l)) => {
                                 trim_new_lines(&mut l);
-                                if updates.send((l, bid, mon)).await.is_err() {
-                                    break
+                                let text = data.output.read(block_name, l);
+                                if updates.send((text, bid, mon)).await.is_err() {
+                                    return output.reap().await;
                                 }
                             }
-                        }
-                        e = events.recv() => {
-                            let Ok(e) = e else {
-                                break;
-                            };
-                            match e {
-                                Event::MouseClicked(id, mon, button) if id == bid => {
-                                    if let Some(a) = actions[button] {
-                                        let _ = cmd::run_cmd(block_name.title, a, mon.into(), current_layer()).await;
-                                    }
-                                }
-                                Event::MouseClicked(..)
-                                | Event::Signal
-                                | Event::NewLayer => (),
-                            }
-                        }
+                            Some(Err(_)) => {}
+                            None => break,
+                        },
+                        _ = shutdown.cancelled() => return output.reap().await,
                     }
                 }
-                output.reap().await;
+                let status = tokio::select! {
+                    status = output.wait() => status,
+                    _ = shutdown.cancelled() => return output.reap().await,
+                };
+                match &status {
+                    Ok(s) => log::error!("persistent command of '{block_name}' exited: {s}"),
+                    Err(e) => {
+                        log::error!("failed to wait on persistent command of '{block_name}': {e}")
+                    }
+                }
+                for line in output.stderr_tail().await {
+                    log::error!("[stderr of {block_name} @ mon:{mon}] {line}");
+                }
+                status
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to start persistent command: '{}', because '{:?}'",
+                    cmd,
+                    e
+                );
+                Err(e)
             }
-        }).await;
+        };
+        if let Some(placeholder) = persistent.placeholder
+            && updates
+                .send((placeholder.to_owned(), bid, mon))
+                .await
+                .is_err()
+        {
+            return;
+        }
+        if !persistent.restart.should_restart(&status) {
+            return;
+        }
+        if started.elapsed() >= Persistent::HEALTHY_AFTER {
+            delay = Persistent::MIN_RESTART_DELAY;
+        }
+        log::info!("restarting persistent command of '{block_name}' in {delay:?}");
+        tokio::select! {
+            _ = time::sleep(delay) => {}
+            _ = shutdown.cancelled() => return,
+        }
+        delay = (delay * 2).min(persistent.max_restart_delay);
+    }
 }
 
 #[pin_project::pin_project]
 struct ChildStream {
     block_name: Title<'static>,
     child: Child,
+    stderr: StderrTail,
     #[pin]
     stream: LinesStream<BufReader<tokio::process::ChildStdout>>,
 }
@@ -101,17 +212,19 @@ impl ChildStream {
     async fn start(
         block_name: Title<'static>,
         cmd: &str,
+        bid: BlockId,
         monitor: AffectedMonitor,
         layer: u16,
     ) -> io::Result<Self> {
         let mut spawned = Command::new("bash")
             .args(["-c", cmd])
             .envs(global_config::get().as_env_vars(monitor, u16::MAX))
+            .envs(super::env_vars(block_name, bid, monitor))
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .kill_on_drop(true)
             .spawn()?;
-        child_debug_loop(
+        let stderr = child_debug_loop(
             spawned.stderr.take().unwrap(),
             block_name.title,
             monitor,
@@ -119,6 +232,7 @@ impl ChildStream {
         );
         Ok(ChildStream {
             block_name,
+            stderr,
             stream: LinesStream::new(
                 BufReader::new(spawned.stdout.take().ok_or(io::ErrorKind::UnexpectedEof)?).lines(),
             ),
@@ -126,6 +240,14 @@ impl ChildStream {
         })
     }
 
+    async fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+
+    async fn stderr_tail(self) -> Vec<String> {
+        self.stderr.finish().await
+    }
+
     async fn reap(mut self) {
         log::debug!("reaping {}", self.block_name);
         match timeout(Duration::from_secs(5), async {
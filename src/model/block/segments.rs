@@ -0,0 +1,130 @@
+use super::{BlockText, Font, TextDecorations};
+use crate::{global_config, model::Color, parsing::parser::Title};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A segment of the text of a block printed by a command with `output: json`. Colors are either
+/// hex codes or the names of the colors of the config.
+#[derive(Deserialize)]
+struct Segment {
+    text: String,
+    fg: Option<String>,
+    bg: Option<String>,
+    underline: Option<String>,
+    /// The index of one of the fonts of the config, as a number or a string.
+    font: Option<Value>,
+    /// Passed to the click actions of the block as `LEMON_SEGMENT`.
+    action: Option<String>,
+}
+
+/// Reads every line of `output` as a json array of segments. Lines that aren't are shown as they
+/// are.
+pub fn parse(block_name: Title<'_>, output: &str) -> Vec<BlockText> {
+    let mut texts = Vec::new();
+    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+        match serde_json::from_str::<Vec<Segment>>(line) {
+            Ok(segments) => texts.extend(segments.into_iter().map(|s| s.into_text(block_name))),
+            Err(e) => {
+                log::warn!("'{block_name}' printed invalid segments '{line}': {e}");
+                texts.push(BlockText::from(line.to_owned()));
+            }
+        }
+    }
+    texts
+}
+
+impl Segment {
+    fn into_text(self, block_name: Title<'_>) -> BlockText {
+        let color = |value: Option<String>| {
+            let value = value?;
+            let color = global_config::get()
+                .get_color(&value)
+                .copied()
+                .ok_or("")
+                .or_else(|_| Color::try_from(value.as_str()));
+            match color {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    log::warn!("'{block_name}' printed an invalid color '{value}': {e}");
+                    None
+                }
+            }
+        };
+        let font = self.font.and_then(|font| {
+            let font = match font {
+                Value::String(s) => s,
+                font => font.to_string(),
+            };
+            match Font::try_from(font.as_str()) {
+                Ok(_) => Some(font),
+                Err(e) => {
+                    log::warn!("'{block_name}' printed an invalid font '{font}': {e}");
+                    None
+                }
+            }
+        });
+        BlockText {
+            decorations: TextDecorations {
+                bg: color(self.bg),
+                fg: color(self.fg),
+                underline: color(self.underline),
+            },
+            text: self.text,
+            segment: self.action,
+            font,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing;
+
+    /// Parses the output of a block with the colors and fonts of the config.
+    fn segments(output: &str) -> Vec<BlockText> {
+        let md = r#"
+# Bar
+- font: mono
+- font: sans
+- colors:
+  - red: #ff0000
+        "#;
+        let _config = parsing::lock_global_config();
+        parsing::parse(md, Default::default()).unwrap();
+        let title = Title {
+            level: 1,
+            title: "segments",
+        };
+        parse(title, output)
+    }
+
+    #[test]
+    fn segment_colors_are_names_or_hex_codes() {
+        let texts =
+            segments(r##"[{"text": "a", "fg": "red", "bg": "#00ff00", "underline": "nope"}]"##);
+        let decorations = texts[0].decorations;
+        assert_eq!(decorations.fg, Some(Color::try_from("#ff0000").unwrap()));
+        assert_eq!(decorations.bg, Some(Color::try_from("#00ff00").unwrap()));
+        assert_eq!(decorations.underline, None);
+    }
+
+    #[test]
+    fn segment_fonts_are_numbers_or_strings() {
+        let texts = segments(
+            r#"[{"text": "a", "font": 1}, {"text": "b", "font": "2"}]
+               [{"text": "c", "font": "x"}, {"text": "d", "font": 0}]"#,
+        );
+        let fonts = texts.iter().map(|t| t.font.as_deref()).collect::<Vec<_>>();
+        assert_eq!(fonts, [Some("1"), Some("2"), None, None]);
+    }
+
+    #[test]
+    fn lines_that_arent_segments_are_shown_as_they_are() {
+        let texts = segments("plain text\n\n[{\"text\": \"a\", \"action\": \"go\"}]");
+        assert_eq!(texts.len(), 2);
+        assert_eq!(texts[0], BlockText::from("plain text".to_owned()));
+        assert_eq!(texts[1].text, "a");
+        assert_eq!(texts[1].segment.as_deref(), Some("go"));
+    }
+}
@@ -1,98 +1,121 @@
-use super::{
-    super::{ActivationLayer, ActiveMonitors},
-    BlockId, Event, Precondition, Signal, TaskData,
-};
+use super::{BlockText, OnError, Precondition, Signal, TaskData, TextDecorations, click};
 use crate::{
-    event_loop::{current_layer, update_task::UpdateChannel},
-    parsing::parser::Title,
-    util::{cmd::run_cmd, result_ext::ResultExt, signal::sig_rt_min, trim_new_lines},
+    event_loop::{Event, current_layer, events::BlockEvents},
+    model::AffectedMonitor,
+    util::{
+        cmd::{log_failure, run_cmd},
+        signal::sig_rt_min,
+        trim_new_lines,
+    },
 };
 use futures::{FutureExt, future::BoxFuture};
-use std::time::Duration;
+use std::{collections::HashMap, future::pending, time::Duration};
 use tokio::{
+    io, select,
     signal::unix::{SignalKind, signal as signal_stream},
-    sync::broadcast,
-    time,
+    time::{self, Instant},
 };
 
 #[derive(Debug, Clone, Copy)]
 pub struct Timed(pub Duration);
 
 impl super::BlockTask for Timed {
-    fn start(&self, events: broadcast::Receiver<Event>, data: TaskData) -> BoxFuture<'static, ()> {
+    fn start(&self, events: BlockEvents, data: TaskData) -> BoxFuture<'static, ()> {
         start(self.0, events, data).boxed()
     }
 }
 
-async fn start(
-    timeout: Duration,
-    mut events: broadcast::Receiver<Event>,
-    TaskData {
-        block_name,
-        cmd,
+async fn start(interval: Duration, mut events: BlockEvents, data: TaskData) {
+    let TaskData {
         updates,
-        actions,
         bid,
         activation_layer,
         monitors,
         signal,
-        precondition,
         ..
-    }: TaskData,
-) {
-    if let Signal::Num(n) = signal {
+    } = &data;
+    let mut state = State::default();
+    let mut clicks = click::Runner::new(&data, &events);
+    if let Signal::Num(n) = *signal {
         let mut signals = match signal_stream(SignalKind::from_raw(sig_rt_min() + n)) {
             Ok(s) => s,
             Err(e) => {
-                return log::error!("Failed to start signal task for '{}' because: {:?}", cmd, e);
+                return log::error!(
+                    "Failed to start signal task for '{}' because: {:?}",
+                    data.cmd,
+                    e
+                );
             }
         };
-        while signals.recv().await.is_some() {
-            if update_blocks(block_name, cmd, activation_layer, bid, monitors, &updates)
-                .await
-                .is_err()
-            {
+        let shutdown = events.shutdown();
+        loop {
+            let next_retry = state.next_retry();
+            let retry = async {
+                match next_retry {
+                    Some(at) => time::sleep_until(at).await,
+                    None => pending().await,
+                }
+            };
+            let updated = select! {
+                s = signals.recv() => match s {
+                    Some(()) => update_blocks(&data, &mut state).await,
+                    None => break,
+                },
+                () = retry => retry_blocks(&data, &mut state).await,
+                () = shutdown.cancelled() => return,
+            };
+            if updated.is_err() {
                 break;
             }
         }
     }
-    if Precondition::holds(&precondition).await
-        && update_blocks(block_name, cmd, activation_layer, bid, monitors, &updates)
-            .await
-            .is_err()
+    if Precondition::holds(&data.precondition).await
+        && update_blocks(&data, &mut state).await.is_err()
     {
         return;
     }
     loop {
-        let event = if activation_layer == current_layer() {
-            time::timeout(timeout, events.recv()).await.ok()
+        let event = if *activation_layer == current_layer() {
+            // blocks that only update on signals have an interval too long to add to now.
+            let deadline = match (Instant::now().checked_add(interval), state.next_retry()) {
+                (Some(deadline), Some(at)) => Some(deadline.min(at)),
+                (deadline, at) => deadline.or(at),
+            };
+            match deadline {
+                Some(deadline) => time::timeout_at(deadline, events.recv()).await.ok(),
+                None => Some(events.recv().await),
+            }
         } else {
             Some(events.recv().await)
         };
+        // failed commands are run again without waiting for the whole interval.
+        let retrying = event.is_none() && state.next_retry().is_some_and(|at| at <= Instant::now());
         if let Some(event) = event {
             match event {
-                Ok(Event::MouseClicked(id, mon, button)) if id == bid => {
-                    if let Some(a) = actions[button] {
-                        let _ = run_cmd(block_name.title, a, mon.into(), current_layer()).await;
-                    }
+                Some(Event::MouseClicked(_, mon, button, segment, count)) => {
+                    clicks.click(mon, button, segment, count);
                     continue;
                 }
-                Ok(Event::Signal) if signal.is_some() => {}
-                Ok(Event::NewLayer) => {}
-                Ok(Event::MouseClicked(..)) | Ok(Event::Signal) => continue,
-                Err(_) => return,
+                Some(Event::Refresh(_)) => {}
+                Some(Event::Signal) if signal.is_some() => {}
+                Some(Event::NewLayer | Event::NewMonitors) => {}
+                Some(Event::Signal) => continue,
+                None => return,
             }
         }
-        if Precondition::holds(&precondition).await {
-            if update_blocks(block_name, cmd, activation_layer, bid, monitors, &updates)
-                .await
-                .is_err()
-            {
+        if Precondition::holds(&data.precondition).await {
+            let updated = if retrying {
+                retry_blocks(&data, &mut state).await
+            } else {
+                update_blocks(&data, &mut state).await
+            };
+            if updated.is_err() {
                 break;
             }
         } else {
+            state.retries.clear();
             for m in monitors.iter() {
-                if updates.send((String::new(), bid, m)).await.is_err() {
+                if updates.send((String::new(), *bid, m)).await.is_err() {
                     break;
                 }
             }
@@ -100,24 +123,170 @@ async fn start(
     }
 }
 
-async fn update_blocks(
-    block_name: Title<'static>,
-    cmd: &'static str,
-    activation_layer: ActivationLayer,
-    bid: BlockId,
-    monitors: ActiveMonitors,
-    updates: &UpdateChannel,
-) -> Result<(), ()> {
+/// How long to wait before retrying a failed command, doubled on every retry up to
+/// [MAX_RETRY_DELAY].
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A command that failed and runs again at `at`, unless the block is updated before that.
+struct Retry {
+    left: u32,
+    delay: Duration,
+    at: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    last_good: HashMap<AffectedMonitor, String>,
+    retries: HashMap<AffectedMonitor, Retry>,
+}
+
+impl State {
+    fn next_retry(&self) -> Option<Instant> {
+        self.retries.values().map(|r| r.at).min()
+    }
+}
+
+async fn update_blocks(data: &TaskData, state: &mut State) -> Result<(), ()> {
     let layer = current_layer();
-    if activation_layer == layer {
-        for m in monitors.iter() {
-            let mut output = run_cmd(block_name.title, cmd, m, layer)
-                .await
-                .map_err(|e| e.to_string())
-                .merge();
-            trim_new_lines(&mut output);
-            updates.send((output, bid, m)).await.map_err(|_| ())?
+    if data.activation_layer == layer {
+        for m in data.monitors.iter() {
+            update_monitor(data, state, m, layer).await?;
         }
     }
     Ok(())
 }
+
+/// Runs the commands whose retry is due.
+async fn retry_blocks(data: &TaskData, state: &mut State) -> Result<(), ()> {
+    let layer = current_layer();
+    if data.activation_layer != layer {
+        state.retries.clear();
+        return Ok(());
+    }
+    let now = Instant::now();
+    let due = state
+        .retries
+        .iter()
+        .filter(|(_, r)| r.at <= now)
+        .map(|(m, _)| *m)
+        .collect::<Vec<_>>();
+    for m in due {
+        update_monitor(data, state, m, layer).await?;
+    }
+    Ok(())
+}
+
+async fn update_monitor(
+    data: &TaskData,
+    state: &mut State,
+    m: AffectedMonitor,
+    layer: u16,
+) -> Result<(), ()> {
+    let env = super::env_vars(data.block_name, data.bid, m);
+    let output = run_cmd(
+        data.block_name.title,
+        data.cmd,
+        m,
+        layer,
+        data.timeout,
+        &env,
+    )
+    .await;
+    let text = match output {
+        Ok(mut output) => {
+            state.retries.remove(&m);
+            trim_new_lines(&mut output);
+            state.last_good.insert(m, output.clone());
+            data.output.read(data.block_name, output)
+        }
+        Err(e) => {
+            log_failure(format_args!("'{}'", data.block_name), &e);
+            let (left, delay) = state
+                .retries
+                .remove(&m)
+                .map_or((data.retries, MIN_RETRY_DELAY), |r| (r.left, r.delay));
+            if left > 0 {
+                log::info!("retrying '{}' in {delay:?}", data.block_name);
+                state.retries.insert(
+                    m,
+                    Retry {
+                        left: left - 1,
+                        delay: (delay * 2).min(MAX_RETRY_DELAY),
+                        at: Instant::now() + delay,
+                    },
+                );
+                return Ok(());
+            }
+            error_text(data, &e, state.last_good.get(&m))
+        }
+    };
+    data.updates.send((text, data.bid, m)).await.map_err(|_| ())
+}
+
+/// What to show after the command failed, according to the block's [OnError] behaviour.
+fn error_text(data: &TaskData, e: &io::Error, last_good: Option<&String>) -> Vec<BlockText> {
+    let mut text = match data.on_error {
+        OnError::Output if e.kind() == io::ErrorKind::TimedOut => data.timeout_marker.to_owned(),
+        OnError::Output => e.to_string(),
+        OnError::Keep => {
+            let last_good = last_good.cloned().unwrap_or_default();
+            let mut texts = data.output.read(data.block_name, last_good);
+            for t in &mut texts {
+                t.decorations.fg = data.error_color.or(t.decorations.fg);
+            }
+            return texts;
+        }
+        OnError::Text(text) => text.to_owned(),
+        OnError::Hide => String::new(),
+    };
+    trim_new_lines(&mut text);
+    vec![BlockText {
+        decorations: TextDecorations {
+            fg: data.error_color,
+            ..Default::default()
+        },
+        text,
+        segment: None,
+        font: None,
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event_loop::events::EventBus,
+        parsing::{lock_global_config, parse},
+    };
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn signal_only_blocks_wait_for_signals() {
+        let config = {
+            let _config = lock_global_config();
+            parse(
+                r#"
+# Bar
+- height: 20
+
+# on signal
+- cmd: `echo hi`
+- signal: true
+- align: left
+                "#,
+                Default::default(),
+            )
+            .unwrap()
+        };
+        let mut events = EventBus::default();
+        let (updates, mut rx) = mpsc::channel(8);
+        let blocks = tokio::spawn(config.start_blocks(&mut events, updates));
+        for _ in 0..2 {
+            let update = rx.recv().await.unwrap();
+            assert_eq!(update.text()[0].text, "hi");
+            events.send(crate::event_loop::Event::Signal);
+        }
+        events.shutdown();
+        blocks.await.unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 use super::{AffectedMonitor, Color};
 use crate::{
-    display::{Bar, CmdlineArgBuilder, Program},
+    display::{Bar, CmdlineArgBuilder, Dialect, Program},
     util::number_as_str,
 };
 use arc_swap::ArcSwap;
@@ -11,7 +11,9 @@ use std::{
     collections::HashMap,
     ffi::{OsStr, OsString},
     iter::once,
+    path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
 pub static GLOBAL_CONFIG: Lazy<ArcSwap<GlobalConfig>> =
@@ -57,6 +59,22 @@ pub struct FileConfig {
     pub underline: Option<Color>,
     #[arg(long)]
     pub separator: Option<String>,
+    /// Minimum time between two redraws of the same bar, in milliseconds
+    #[arg(long)]
+    pub frame_interval: Option<u64>,
+    /// Longest time between the two clicks of a double click, in milliseconds
+    #[arg(long)]
+    pub double_click_interval: Option<u64>,
+    /// Redraw the bar even when an update didn't change anything, which works around zelbar
+    /// bugs. On by default for zelbar
+    #[arg(long)]
+    pub redundant_updates: Option<bool>,
+    /// How long commands can run before being killed, in seconds
+    #[arg(long)]
+    pub timeout: Option<u64>,
+    /// Overriden by the outputs passed on the command line
+    #[arg(skip)]
+    pub outputs: Vec<String>,
     // hard to pass arguments
     #[arg(skip)]
     colors: HashMap<String, (String, Color)>,
@@ -64,13 +82,28 @@ pub struct FileConfig {
 
 #[derive(Default, Clone, Debug, Args)]
 pub struct CommandLineConfig {
-    /// The parameters to pass to each bar
+    /// The outputs to draw a bar on, `auto` draws one on every connected monitor
     #[arg(short, long("output"))]
     pub outputs: Vec<String>,
     #[arg(long)]
     pub tray: bool,
+    /// The bar to draw on: lemonbar, zelbar, dzen2, xmobar, stdout (writes the lines to stdout
+    /// and reads click actions from stdin), i3bar (the i3bar protocol, for i3bar and swaybar),
+    /// waybar (a custom module showing the block given with --block), tmux (its status line) or
+    /// somebar (its status)
     #[arg(short, long, default_value = "lemonbar")]
     pub program: Program,
+    /// How the lines written by `--program stdout` are formatted: plain, lemonbar, zelbar or
+    /// pango (markup for somebar or a yambar script), plain by default
+    #[arg(long)]
+    pub dialect: Option<Dialect>,
+    /// Only run the block with this title
+    #[arg(long)]
+    pub block: Option<String>,
+    /// Write the tmux status line to this file, for `#(cat <file>)` in tmux's status-format,
+    /// instead of setting status-left and status-right
+    #[arg(long)]
+    pub tmux_file: Option<PathBuf>,
 }
 
 impl GlobalConfig {
@@ -93,14 +126,35 @@ impl GlobalConfig {
                 foreground: overrides.file_config.foreground.or(file_config.foreground),
                 underline: overrides.file_config.underline.or(file_config.underline),
                 separator: overrides.file_config.separator.or(file_config.separator),
+                frame_interval: overrides
+                    .file_config
+                    .frame_interval
+                    .or(file_config.frame_interval),
+                double_click_interval: overrides
+                    .file_config
+                    .double_click_interval
+                    .or(file_config.double_click_interval),
+                redundant_updates: overrides
+                    .file_config
+                    .redundant_updates
+                    .or(file_config.redundant_updates),
+                timeout: overrides.file_config.timeout.or(file_config.timeout),
+                outputs: file_config.outputs.clone(),
                 colors: file_config.colors,
             },
-            cmdline: overrides.cmdline,
+            cmdline: CommandLineConfig {
+                outputs: if overrides.cmdline.outputs.is_empty() {
+                    file_config.outputs
+                } else {
+                    overrides.cmdline.outputs
+                },
+                ..overrides.cmdline
+            },
             n_layers: 0,
         }
     }
 
-    pub fn to_arg_list<W, B>(&self, output: Option<&str>) -> Vec<String>
+    pub fn to_arg_list<W, B>(&self, output: Option<&str>) -> Result<Vec<String>, String>
     where
         B: Bar<W>,
         W: std::fmt::Write,
@@ -110,7 +164,7 @@ impl GlobalConfig {
             arg_builder.height(h)
         }
         if let Some(o) = output {
-            arg_builder.output(o)
+            arg_builder.output(o)?
         }
         if self.file_config.bottom {
             arg_builder.bottom();
@@ -131,7 +185,14 @@ impl GlobalConfig {
         if let Some(un) = &self.file_config.underline {
             arg_builder.underline_color(un)
         }
-        arg_builder.finish()
+        Ok(arg_builder.finish())
+    }
+
+    /// Whether the bars are redrawn even when an update didn't change anything.
+    pub fn redundant_updates(&self) -> bool {
+        self.file_config
+            .redundant_updates
+            .unwrap_or(self.cmdline.program == Program::Zelbar)
     }
 
     pub fn get_color<'s>(&'s self, name: &str) -> Option<&'s Color> {
@@ -178,6 +239,25 @@ impl GlobalConfig {
 }
 
 impl FileConfig {
+    const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+    const DEFAULT_DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(300);
+
+    pub fn frame_interval(&self) -> Duration {
+        self.frame_interval
+            .map(Duration::from_millis)
+            .unwrap_or(Self::DEFAULT_FRAME_INTERVAL)
+    }
+
+    pub fn double_click_interval(&self) -> Duration {
+        self.double_click_interval
+            .map(Duration::from_millis)
+            .unwrap_or(Self::DEFAULT_DOUBLE_CLICK_INTERVAL)
+    }
+
+    pub fn command_timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
     pub fn get_color<'s>(&'s self, name: &str) -> Option<&'s Color> {
         self.colors.get(name).map(|x| &x.1)
     }
@@ -4,29 +4,49 @@ pub mod color;
 pub mod global_config;
 pub mod monitor;
 
-use crate::event_loop::Event;
+use crate::event_loop::events::EventBus;
 pub use alignment::Alignment;
-use block::{Block, BlockUpdate};
+use block::{Block, BlockId, BlockUpdate};
 pub use color::Color;
 use core::fmt;
 use futures::{StreamExt, stream::FuturesUnordered};
 pub use monitor::ActiveMonitors;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     future::Future,
     ops::{Index, IndexMut},
 };
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::mpsc;
 
 #[derive(Default)]
 pub struct Config<'a>(pub [Vec<Block<'a>>; 3]);
 
 impl Config<'static> {
+    /// The id of every block, by title.
+    pub fn ids(&self) -> HashMap<&'static str, BlockId> {
+        self.0
+            .iter()
+            .flat_map(|blocks| blocks.iter().enumerate())
+            .map(|(index, b)| (b.title().title, (b.alignment, index)))
+            .collect()
+    }
+
+    /// Drops every block but the one titled `title`, returns whether there was one.
+    pub fn keep_only(&mut self, title: &str) -> bool {
+        for blocks in &mut self.0 {
+            blocks.retain(|b| b.title().title == title);
+        }
+        self.0.iter().any(|blocks| !blocks.is_empty())
+    }
+
     pub fn start_blocks(
         &self,
-        broadcast: &broadcast::Sender<Event>,
+        events: &mut EventBus,
         responses: mpsc::Sender<BlockUpdate>,
     ) -> impl Future<Output = ()> + use<> {
         let mut indexes = Indexes::default();
+        let ids = self.ids();
         let futures = self
             .0
             .iter()
@@ -34,11 +54,14 @@ impl Config<'static> {
             .map(|b| {
                 let task_as_str = format!("{:?}", b.task);
                 let bid = (b.alignment, indexes.get(b.alignment));
-                let broadcast = broadcast.subscribe();
+                for button in b.double_clicks() {
+                    events.bind_double_click(bid, button);
+                }
+                let events = events.register(bid);
                 let updates = responses.clone().into();
                 let cmd = b.cmd;
 
-                let fut = b.start(bid, broadcast, updates);
+                let fut = b.start(bid, events, updates, &ids);
 
                 async move {
                     log::info!("Starting task {task_as_str}({}) {bid:?}", cmd);
@@ -118,7 +141,7 @@ impl fmt::Display for ActivationLayer {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AffectedMonitor {
     All,
     Single(u8),
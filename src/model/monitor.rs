@@ -1,6 +1,6 @@
-use std::num::{NonZeroU8, NonZeroUsize};
+use std::num::NonZeroUsize;
 
-use crate::util::one_or_more::OneOrMore;
+use crate::{event_loop::monitor_count, util::one_or_more::OneOrMore};
 
 use super::AffectedMonitor;
 
@@ -8,34 +8,36 @@ use super::AffectedMonitor;
 pub enum ActiveMonitors {
     #[default]
     All,
-    MonitorCount(NonZeroU8),
+    /// One instance per bar, following monitors as they are plugged in and out.
+    PerMonitor,
 }
 
 impl ActiveMonitors {
     pub fn resize_one_or_more<T: Default>(self, one_or_more: &mut OneOrMore<T>) {
-        if let Self::MonitorCount(m) = self {
-            one_or_more.resize_with(m.get() as usize, Default::default)
+        if let Self::PerMonitor = self
+            && one_or_more.len() < usize::from(monitor_count())
+        {
+            one_or_more.resize_with(monitor_count().into(), Default::default)
         }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = AffectedMonitor> + use<> {
-        let u8_range = match self {
-            Self::All => u8::MAX..=u8::MAX,
-            Self::MonitorCount(m) => 0..=(m.get() - 1),
+        let range = match self {
+            Self::All => u16::from(u8::MAX)..u16::from(u8::MAX) + 1,
+            Self::PerMonitor => 0..u16::from(monitor_count()),
         };
-        u8_range.map(|m| {
-            if m == u8::MAX {
-                AffectedMonitor::All
-            } else {
-                AffectedMonitor::Single(m)
-            }
+        range.map(|m| match u8::try_from(m) {
+            Ok(m) if m != u8::MAX => AffectedMonitor::Single(m),
+            _ => AffectedMonitor::All,
         })
     }
 
     pub fn len(&self) -> NonZeroUsize {
         match self {
             Self::All => NonZeroUsize::new(1).unwrap(),
-            Self::MonitorCount(n) => NonZeroUsize::from(*n),
+            Self::PerMonitor => {
+                NonZeroUsize::new(monitor_count().into()).unwrap_or(NonZeroUsize::MIN)
+            }
         }
     }
 }
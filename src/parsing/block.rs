@@ -3,16 +3,19 @@ use super::{
     parser::{KeyValues, Title},
 };
 use crate::{
+    event_loop::{
+        MouseButton,
+        internal_task::{self, Internal, LayerChange},
+    },
     global_config,
     model::{
         ActivationLayer, ActiveMonitors, Alignment,
-        block::{self, *},
+        block::{self, persistent::RestartPolicy, *},
     },
     util::signal::valid_rt_signum,
 };
 use std::{
-    convert::TryInto, num::NonZeroU8, path::Path, result::Result as StdResult, str::FromStr,
-    time::Duration,
+    convert::TryInto, path::Path, result::Result as StdResult, str::FromStr, time::Duration,
 };
 
 impl FromStr for Alignment {
@@ -40,6 +43,117 @@ impl FromStr for ActivationLayer {
     }
 }
 
+impl FromStr for RestartPolicy {
+    type Err = &'static str;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "on-failure" => Ok(Self::OnFailure),
+            "never" => Ok(Self::Never),
+            _ => Err("Invalid restart policy"),
+        }
+    }
+}
+
+impl FromStr for OnError<'_> {
+    type Err = &'static str;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "output" => Ok(Self::Output),
+            "keep" => Ok(Self::Keep),
+            "hide" => Ok(Self::Hide),
+            _ => Err("Invalid on_error behaviour"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("Invalid output format"),
+        }
+    }
+}
+
+impl FromStr for click::ActionPolicy {
+    type Err = &'static str;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "queue" => Ok(Self::Queue),
+            "drop-while-running" => Ok(Self::DropWhileRunning),
+            "cancel-previous" => Ok(Self::CancelPrevious),
+            _ => Err("Invalid action policy"),
+        }
+    }
+}
+
+impl FromStr for Internal {
+    type Err = &'static str;
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let (action, arg) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        let arg = arg.trim();
+        match action {
+            "layer" => Ok(Self::Layer(match arg.split_once(' ') {
+                None if arg == "next" => LayerChange::Next,
+                None if arg == "prev" => LayerChange::Prev,
+                Some(("set", n)) => {
+                    LayerChange::Set(n.trim().parse().map_err(|_| "Invalid layer")?)
+                }
+                _ => return Err("Expected 'next', 'prev' or 'set N'"),
+            })),
+            "refresh" | "toggle" if arg.is_empty() => Err("Missing block title"),
+            "refresh" => Ok(Self::Refresh(arg.to_owned())),
+            "toggle" => Ok(Self::Toggle(arg.to_owned())),
+            "reload" => Ok(Self::Reload),
+            "quit" => Ok(Self::Quit),
+            _ => Err("Invalid internal action"),
+        }
+    }
+}
+
+/// Checks that actions handled by lemon itself are valid, other actions are run with bash.
+fn action(value: &str) -> Result<'_, &str> {
+    if let Some(internal) = value.strip_prefix(internal_task::PREFIX) {
+        internal
+            .parse::<Internal>()
+            .map_err(|error| ParseError::InvalidInternalAction { value, error })?;
+    }
+    Ok(value)
+}
+
+/// The keys that bind a command to each mouse button, in the order of [MouseButton].
+const ACTION_KEYS: [&str; MouseButton::COUNT] = [
+    "left-click",
+    "middle-click",
+    "right-click",
+    "scroll-up",
+    "scroll-down",
+    "scroll-left",
+    "scroll-right",
+    "back",
+    "forward",
+    "double-left-click",
+    "double-middle-click",
+    "double-right-click",
+];
+
+/// Parses a comma separated list of `output`, `refresh` and `refresh <block title>`.
+fn parse_after_click(value: &str) -> Result<'_, AfterClick<&str>> {
+    let mut after = AfterClick::default();
+    for modifier in value.split(',').map(str::trim) {
+        match modifier.split_once(' ') {
+            None if modifier == "output" => after.show_output = true,
+            None if modifier == "refresh" => after.refresh = true,
+            Some(("refresh", title)) => after.refresh_others.push(title.trim()),
+            _ => return Err(ParseError::InvalidAfterClick(modifier)),
+        }
+    }
+    Ok(after)
+}
+
 enum BlockType {
     Static,
     Cmd,
@@ -58,18 +172,25 @@ enum BlockType {
 impl Block<'static> {
     pub fn from_kvs(
         title: Title<'static>,
-        n_monitors: NonZeroU8,
         // indexes: &mut Indexes,
         iter: KeyValues<'static, '_>,
     ) -> Result<'static, Self> {
         let mut decorations_b = TextDecorations::default();
         let mut block_b = BlockBuilder::default();
         let mut actions: Actions<'static> = Default::default();
+        let mut after_click: [AfterClick<&'static str>; MouseButton::COUNT] = Default::default();
+        let mut click_limits = [ClickLimit::default(); MouseButton::COUNT];
         // mandatory parameters
         let mut alignment = None;
         let mut cmd = None;
         let mut interval = None;
+        // persistent blocks
+        let mut restart = RestartPolicy::default();
+        let mut max_restart_delay = block::persistent::Persistent::DEFAULT_MAX_RESTART_DELAY;
+        let mut placeholder = None;
         let gc = global_config::get();
+        let mut timeout = gc.file_config.command_timeout();
+        let mut error_color = gc.get_color("error").copied();
         for kvl in iter {
             let (key, value, _) = kvl?;
             log::trace!("{}: {}", key, value);
@@ -104,28 +225,93 @@ impl Block<'static> {
                             .map_err(|_| ParseError::InvalidOffset(value))?,
                     );
                 }
-                "left-click" => {
-                    actions[0] = Some(value);
+                key if ACTION_KEYS.contains(&key) => {
+                    let i = ACTION_KEYS.iter().position(|k| *k == key).unwrap();
+                    actions[i] = Some(action(value)?);
+                }
+                key if key
+                    .rsplit_once('-')
+                    .is_some_and(|(button, _)| ACTION_KEYS.contains(&button)) =>
+                {
+                    let (button, option) = key.rsplit_once('-').unwrap();
+                    let i = ACTION_KEYS.iter().position(|k| *k == button).unwrap();
+                    let millis = || {
+                        value
+                            .parse()
+                            .map(Duration::from_millis)
+                            .map_err(|_| ParseError::InvalidDuration(value))
+                    };
+                    match option {
+                        "then" => after_click[i] = parse_after_click(value)?,
+                        "throttle" => click_limits[i].throttle = Some(millis()?),
+                        "debounce" => click_limits[i].debounce = Some(millis()?),
+                        "accumulate" => {
+                            click_limits[i].accumulate = value
+                                .parse()
+                                .map_err(|_| ParseError::InvalidBoolean(value))?
+                        }
+                        _ => log::warn!("unrecognised option '{}', skipping", key),
+                    }
                 }
-                "middle-click" => {
-                    actions[1] = Some(value);
+                "interval" => {
+                    interval = Some(Duration::from_secs(
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| ParseError::InvalidDuration(value))?,
+                    ));
                 }
-                "right-click" => {
-                    actions[2] = Some(value);
+                "restart" => {
+                    restart = value
+                        .parse()
+                        .map_err(|_| ParseError::InvalidRestartPolicy(value))?;
                 }
-                "scroll-up" => {
-                    actions[3] = Some(value);
+                "restart_max_delay" => {
+                    max_restart_delay = Duration::from_secs(
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| ParseError::InvalidDuration(value))?,
+                    );
                 }
-                "scroll-down" => {
-                    actions[4] = Some(value);
+                "placeholder" => {
+                    placeholder = Some(value);
                 }
-                "interval" => {
-                    interval = Some(Duration::from_secs(
+                "timeout" => {
+                    timeout = Some(Duration::from_secs(
                         value
                             .parse::<u64>()
                             .map_err(|_| ParseError::InvalidDuration(value))?,
                     ));
                 }
+                "timeout_marker" => {
+                    block_b.timeout_marker(value);
+                }
+                "on_error" => {
+                    block_b.on_error(
+                        value
+                            .parse()
+                            .map_err(|_| ParseError::InvalidOnError(value))?,
+                    );
+                }
+                "action_policy" => {
+                    block_b.action_policy(
+                        value
+                            .parse()
+                            .map_err(|_| ParseError::InvalidActionPolicy(value))?,
+                    );
+                }
+                "error_text" => {
+                    block_b.on_error(OnError::Text(value));
+                }
+                "error_color" => {
+                    error_color = Some(color()?);
+                }
+                "retries" => {
+                    block_b.retries(
+                        value
+                            .parse()
+                            .map_err(|_| ParseError::InvalidInteger(value))?,
+                    );
+                }
                 "command" | "cmd" => {
                     cmd = Some((value, BlockType::Cmd));
                 }
@@ -168,13 +354,20 @@ impl Block<'static> {
                             .map_err(|_| ParseError::InvalidBoolean(value))?,
                     );
                 }
+                "output" => {
+                    block_b.output(
+                        value
+                            .parse()
+                            .map_err(|_| ParseError::InvalidOutputFormat(value))?,
+                    );
+                }
                 "multi_monitor" => {
                     block_b.active_in(
                         if value
                             .parse()
                             .map_err(|_| ParseError::InvalidBoolean(value))?
                         {
-                            ActiveMonitors::MonitorCount(n_monitors)
+                            ActiveMonitors::PerMonitor
                         } else {
                             ActiveMonitors::All
                         },
@@ -195,6 +388,9 @@ impl Block<'static> {
                         _ => return Err(ParseError::InvalidPrecondition(cond)),
                     }
                 }
+                key if key.starts_with("long-") => {
+                    log::warn!("'{key}' isn't supported, bars can't report long presses, skipping");
+                }
                 s => {
                     log::warn!("unrecognised option '{}', skipping", s);
                 }
@@ -202,6 +398,14 @@ impl Block<'static> {
         }
         if let Some(((value, kind), alignment)) = (|| Some((cmd?, alignment?)))() {
             block_b.decorations(decorations_b);
+            block_b.after_click(after_click);
+            block_b.click_limits(click_limits);
+            if let Some(timeout) = timeout {
+                block_b.timeout(timeout);
+            }
+            if let Some(error_color) = error_color {
+                block_b.error_color(error_color);
+            }
 
             let task: Box<dyn BlockTask> = match kind {
                 BlockType::Static => Box::new(block::constant::Static),
@@ -216,11 +420,15 @@ impl Block<'static> {
                         "Missing either signal or interval".into(),
                     ));
                 }
-                BlockType::Persistent => Box::new(block::persistent::Persistent),
+                BlockType::Persistent => Box::new(block::persistent::Persistent {
+                    restart,
+                    max_restart_delay,
+                    placeholder,
+                }),
                 BlockType::Native => {
                     #[cfg(feature = "hyprland")]
                     if value == block::native::native_block::HYPRLAND {
-                        block_b.active_in(ActiveMonitors::MonitorCount(n_monitors));
+                        block_b.active_in(ActiveMonitors::PerMonitor);
                     }
                     match block::native::new(value) {
                         Some(b) => b,
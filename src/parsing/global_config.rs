@@ -40,6 +40,38 @@ impl FileConfig {
                     )
                 }
                 "separator" => global_config.separator = Some(value.to_owned()),
+                "frame_interval" => {
+                    global_config.frame_interval = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| ParseError::InvalidDuration(value))?,
+                    )
+                }
+                "double_click_interval" => {
+                    global_config.double_click_interval = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| ParseError::InvalidDuration(value))?,
+                    )
+                }
+                "timeout" => {
+                    global_config.timeout = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| ParseError::InvalidDuration(value))?,
+                    )
+                }
+                "redundant_updates" => {
+                    global_config.redundant_updates = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| ParseError::InvalidBoolean(value))?,
+                    )
+                }
                 "height" | "h" => {
                     global_config.height = Some(
                         value
@@ -49,6 +81,9 @@ impl FileConfig {
                     )
                 }
                 "name" | "n" => global_config.name = Some(value.to_owned()),
+                "outputs" | "output" | "o" => global_config
+                    .outputs
+                    .extend(value.split(',').map(|o| o.trim().to_owned())),
                 "colors" | "colours" | "c" => in_colors = true,
                 key if level == 2 && in_colors => {
                     global_config.set_color(key, color()?);
@@ -3,8 +3,6 @@ mod color;
 mod global_config;
 pub mod parser;
 
-use std::num::NonZeroU8;
-
 use crate::{
     Config,
     global_config::{FileConfig, GlobalConfig},
@@ -33,6 +31,14 @@ pub enum ParseError<'a> {
     NeedAtLeastOneBarSpec,
     InvalidPrecondition(&'a str),
     InvalidPreconditionArgument(&'a str),
+    InvalidRestartPolicy(&'a str),
+    InvalidOnError(&'a str),
+    InvalidActionPolicy(&'a str),
+    InvalidOutputFormat(&'a str),
+    InvalidAfterClick(&'a str),
+    InvalidInternalAction { value: &'a str, error: &'static str },
+    UnknownBlock(String),
+    DuplicateTitle(&'a str),
 }
 
 pub type Result<'a, T> = std::result::Result<T, ParseError<'a>>;
@@ -48,22 +54,24 @@ pub fn parse(config: &'static str, overrides: GlobalConfig) -> Result<'static, C
 
     let mut blocks = Config::default();
     crate::global_config::set(global_config.clone());
-    let bar_spec_count = global_config
-        .cmdline
-        .outputs
-        .len()
-        .try_into()
-        .map_err(|_| ParseError::TooManyBarSpecs {
+    if u8::try_from(global_config.cmdline.outputs.len()).is_err() {
+        return Err(ParseError::TooManyBarSpecs {
             got: global_config.cmdline.outputs.len(),
             max: u8::MAX,
-        })
-        .map(NonZeroU8::new)?
-        .unwrap_or_else(|| NonZeroU8::new(1).unwrap());
+        });
+    }
     while let Some((title, kvs)) = parser.next_section()? {
+        // blocks refer to each other by title.
+        if blocks
+            .0
+            .iter()
+            .flatten()
+            .any(|b| b.title().title == title.title)
+        {
+            return Err(ParseError::DuplicateTitle(title.title));
+        }
         let block = Block::from_kvs(
-            title,
-            bar_spec_count,
-            // &mut indexes,
+            title, // &mut indexes,
             kvs,
             // broadcast,
             // responses,
@@ -73,8 +81,49 @@ pub fn parse(config: &'static str, overrides: GlobalConfig) -> Result<'static, C
         }
         blocks[block.alignment].push(block);
     }
+    if let Some(title) = &global_config.cmdline.block
+        && !blocks.keep_only(title)
+    {
+        return Err(ParseError::UnknownBlock(title.clone()));
+    }
     global_config.n_layers += 1;
     log::debug!("global config loaded: {global_config:?}");
     crate::global_config::set(global_config);
     Ok(blocks)
 }
+
+/// Held by the tests that parse a config, parsing replaces the global one.
+#[cfg(test)]
+pub(crate) fn lock_global_config() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_titles() {
+        let _config = lock_global_config();
+        let md = r#"
+# Bar
+- height: 20
+
+# clock
+- cmd: `date`
+- interval: 1
+- align: left
+
+# clock
+- cmd: `date +%s`
+- interval: 1
+- align: right
+        "#;
+        assert!(matches!(
+            parse(md, Default::default()).err(),
+            Some(ParseError::DuplicateTitle("clock"))
+        ));
+    }
+}
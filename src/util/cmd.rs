@@ -1,57 +1,164 @@
+use super::signal::kill_process_group;
 use crate::{
     global_config,
     model::{ActivationLayer, AffectedMonitor},
 };
-use std::process::Stdio;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    process::{ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::{
     io::{self, AsyncBufReadExt as _, BufReader},
     process::{ChildStderr, Command},
+    task::JoinHandle,
+    time::timeout,
 };
 use tokio_stream::{StreamExt, wrappers::LinesStream};
 
+/// A command that ran to completion but exited unsuccessfully.
+#[derive(Debug)]
+pub struct CmdFailed {
+    pub status: ExitStatus,
+    pub stdout: String,
+}
+
+impl CmdFailed {
+    /// The [CmdFailed] wrapped by `e`, if any.
+    pub fn from_io(e: &io::Error) -> Option<&Self> {
+        e.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for CmdFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.stdout)
+    }
+}
+
+impl Error for CmdFailed {}
+
+/// Kills the process group of a command when dropped, unless the command finished.
+struct ProcessGroupGuard(Option<u32>);
+
+impl ProcessGroupGuard {
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0 {
+            kill_process_group(pgid);
+        }
+    }
+}
+
+/// Runs `cmd` with bash, killing it and everything it spawned if it takes longer than
+/// `time_limit`, in which case an error of kind [io::ErrorKind::TimedOut] is returned.
+///
+/// If the command exits unsuccessfully the error wraps a [CmdFailed].
 pub async fn run_cmd(
     source_block_name: &'static str,
     cmd: &str,
     monitor: AffectedMonitor,
     layer: u16,
+    time_limit: Option<Duration>,
+    env: &[(&str, String)],
 ) -> io::Result<String> {
     let mut spawned = Command::new("bash")
         .args(["-c", cmd])
         .envs(global_config::get().as_env_vars(monitor, layer))
+        .envs(env.iter().map(|(k, v)| (*k, v)))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        // run in a new process group so that whatever the command spawned can be killed with it.
+        .process_group(0)
         // although we wait for the child to terminate, we can't guarantee that
         // this future is not canceled, so we should enable kill on drop in
         // case the future is canceled to make this cancel safer.
         .kill_on_drop(true)
         .spawn()?;
+    let guard = ProcessGroupGuard(spawned.id());
     child_debug_loop(
         spawned.stderr.take().unwrap(),
         source_block_name,
         monitor,
         ActivationLayer::L(layer),
     );
-    let output = spawned.wait_with_output().await?;
+    let output = match time_limit {
+        Some(limit) => match timeout(limit, spawned.wait_with_output()).await {
+            Ok(output) => output?,
+            Err(_elapsed) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("'{cmd}' timed out after {limit:?}"),
+                ));
+            }
+        },
+        None => spawned.wait_with_output().await?,
+    };
+    guard.disarm();
     if !output.status.success() {
-        return Err(io::Error::other(String::from_utf8_lossy(&output.stdout)));
+        return Err(io::Error::other(CmdFailed {
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        }));
     }
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
+/// Logs why the command of `what` (e.g. a block or one of its actions) didn't succeed.
+pub fn log_failure(what: impl fmt::Display, e: &io::Error) {
+    match CmdFailed::from_io(e) {
+        Some(failed) => log::warn!("{what} failed: {}", failed.status),
+        None if e.kind() == io::ErrorKind::TimedOut => log::warn!("{what}: {e}"),
+        None => log::error!("{what} couldn't run: {e}"),
+    }
+}
+
+/// The last few lines a child wrote to its stderr.
+pub struct StderrTail {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    reader: JoinHandle<io::Result<()>>,
+}
+
+impl StderrTail {
+    const LINES: usize = 10;
+
+    /// Waits a little for the child's stderr to be closed and returns the last lines it wrote.
+    pub async fn finish(self) -> Vec<String> {
+        let _ = timeout(Duration::from_secs(1), self.reader).await;
+        self.lines.lock().unwrap().drain(..).collect()
+    }
+}
+
 pub fn child_debug_loop(
     stderr: ChildStderr,
     name: &'static str,
     monitor: AffectedMonitor,
     layer: ActivationLayer,
-) {
-    if log::log_enabled!(log::Level::Debug) {
-        tokio::spawn(async move {
+) -> StderrTail {
+    let lines = Arc::new(Mutex::new(VecDeque::with_capacity(StderrTail::LINES)));
+    let reader = tokio::spawn({
+        let lines = lines.clone();
+        async move {
             let mut stderr = LinesStream::new(BufReader::new(stderr).lines());
             while let Some(line) = stderr.next().await.transpose()? {
-                log::debug!("[stderr of {name} @ mon:{monitor} in layer:{layer}] {line}")
+                log::debug!("[stderr of {name} @ mon:{monitor} in layer:{layer}] {line}");
+                let mut lines = lines.lock().unwrap();
+                if lines.len() == StderrTail::LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
             }
 
             Ok::<_, io::Error>(())
-        });
-    }
+        }
+    });
+    StderrTail { lines, reader }
 }
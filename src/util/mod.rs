@@ -1,6 +1,5 @@
 pub mod cmd;
 pub mod one_or_more;
-pub mod result_ext;
 pub mod signal;
 
 static NUMBERS: [&str; u8::MAX as usize + 1] = [
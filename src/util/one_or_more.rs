@@ -48,6 +48,20 @@ impl<T> OneOrMore<T> {
         }
     }
 
+    pub fn get(&self, i: usize) -> Option<&T> {
+        match self {
+            Self::One(t) => Some(t),
+            Self::More(m) => m.get(i),
+        }
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        match self {
+            Self::One(t) => Some(t),
+            Self::More(m) => m.get_mut(i),
+        }
+    }
+
     pub fn iter(&self) -> Iter<'_, T> {
         match self {
             Self::One(t) => Iter::One(Some(t)),
@@ -16,3 +16,10 @@ pub fn sig_rt_max() -> c_int {
 pub fn valid_rt_signum(x: c_int) -> bool {
     sig_rt_min() + x < sig_rt_max()
 }
+
+/// Kills every process in the process group `pgid`.
+pub fn kill_process_group(pgid: u32) {
+    if let Ok(pgid) = libc::pid_t::try_from(pgid) {
+        unsafe { libc::killpg(pgid, libc::SIGKILL) };
+    }
+}